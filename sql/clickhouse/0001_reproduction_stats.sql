@@ -0,0 +1,17 @@
+CREATE TABLE tribesim.simulation_yearly_reproduction_stats
+(
+    `simulation_id` String,
+    `year` UInt32,
+    `eligible_agents` UInt64,
+    `pairs` UInt64,
+    `births` UInt64,
+    `failed_attempts` UInt64,
+    `mean_parental_resources` Float64,
+    `mean_child_cost` Float64,
+    `mean_child_endowment` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY simulation_id
+ORDER BY (simulation_id, year)
+SETTINGS index_granularity = 8192;
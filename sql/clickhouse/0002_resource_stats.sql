@@ -0,0 +1,34 @@
+CREATE TABLE tribesim.simulation_yearly_resource_stats
+(
+    `simulation_id` String,
+    `year` UInt32,
+    `total_distributed` Float64,
+    `gini` Float64,
+    `spent_hunting` Float64,
+    `spent_food` Float64,
+    `spent_useless` Float64,
+    `spent_reproduction` Float64,
+    `lost_with_dead` Float64,
+    `lost_with_dissolved` Float64,
+    `expired` Float64,
+    `total_held` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY simulation_id
+ORDER BY (simulation_id, year)
+SETTINGS index_granularity = 8192;
+
+CREATE TABLE tribesim.simulation_yearly_group_stats
+(
+    `simulation_id` String,
+    `year` UInt32,
+    `group_id` UInt64,
+    `headcount` UInt64,
+    `resource_share` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY simulation_id
+ORDER BY (simulation_id, year, group_id)
+SETTINGS index_granularity = 8192;
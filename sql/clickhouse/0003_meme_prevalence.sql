@@ -0,0 +1,7 @@
+ALTER TABLE tribesim.simulation_yearly_meme_stats
+    ADD COLUMN IF NOT EXISTS `copies` UInt64 AFTER `avg_meme_size`,
+    ADD COLUMN IF NOT EXISTS `distinct_memes` UInt64 AFTER `copies`,
+    ADD COLUMN IF NOT EXISTS `carrier_fraction` Float64 AFTER `distinct_memes`,
+    ADD COLUMN IF NOT EXISTS `inventions` UInt64 AFTER `carrier_fraction`,
+    ADD COLUMN IF NOT EXISTS `transfers` UInt64 AFTER `inventions`,
+    ADD COLUMN IF NOT EXISTS `losses` UInt64 AFTER `transfers`;
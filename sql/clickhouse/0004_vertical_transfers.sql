@@ -0,0 +1,2 @@
+ALTER TABLE tribesim.simulation_yearly_meme_stats
+    ADD COLUMN IF NOT EXISTS `vertical_transfers` UInt64 AFTER `transfers`;
@@ -0,0 +1,2 @@
+ALTER TABLE tribesim.simulation_yearly_group_stats
+    ADD COLUMN IF NOT EXISTS `patch` Nullable(UInt32) AFTER `group_id`;
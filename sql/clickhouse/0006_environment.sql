@@ -0,0 +1,3 @@
+ALTER TABLE tribesim.simulation_yearly_global_stats
+    ADD COLUMN IF NOT EXISTS `resources` Float64 AFTER `avg_meme_size`,
+    ADD COLUMN IF NOT EXISTS `shock` Bool AFTER `resources`;
@@ -0,0 +1,14 @@
+CREATE TABLE tribesim.simulation_yearly_conflict_stats
+(
+    `simulation_id` String,
+    `year` UInt32,
+    `conflicts` UInt64,
+    `attacker_wins` UInt64,
+    `casualties` UInt64,
+    `resources_looted` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY simulation_id
+ORDER BY (simulation_id, year)
+SETTINGS index_granularity = 8192;
@@ -0,0 +1,6 @@
+ALTER TABLE tribesim.simulation_yearly_global_stats
+    ADD COLUMN IF NOT EXISTS `max_meme_depth` UInt32 AFTER `shock`,
+    ADD COLUMN IF NOT EXISTS `avg_meme_depth` Float64 AFTER `max_meme_depth`;
+
+ALTER TABLE tribesim.simulation_yearly_meme_stats
+    ADD COLUMN IF NOT EXISTS `max_depth` UInt32 AFTER `losses`;
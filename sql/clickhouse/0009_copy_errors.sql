@@ -0,0 +1,2 @@
+ALTER TABLE tribesim.simulation_yearly_meme_stats
+    ADD COLUMN IF NOT EXISTS `copy_errors` UInt64 AFTER `losses`;
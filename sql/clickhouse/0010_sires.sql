@@ -0,0 +1,2 @@
+ALTER TABLE tribesim.simulation_yearly_reproduction_stats
+    ADD COLUMN IF NOT EXISTS `sires` UInt64 AFTER `births`;
@@ -0,0 +1,18 @@
+CREATE TABLE tribesim.simulation_yearly_migration_stats
+(
+    `simulation_id` String,
+    `year` UInt32,
+    `migrants` UInt64,
+    `mean_migrant_age` Float64,
+    `memes_carried` UInt64,
+    `memes_lost` UInt64,
+    `resources_lost` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY simulation_id
+ORDER BY (simulation_id, year)
+SETTINGS index_granularity = 8192;
+
+ALTER TABLE tribesim.simulation_yearly_resource_stats
+    ADD COLUMN IF NOT EXISTS `lost_in_migration` Float64 AFTER `lost_with_dissolved`;
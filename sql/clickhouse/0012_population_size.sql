@@ -0,0 +1,3 @@
+ALTER TABLE tribesim.simulation_yearly_global_stats
+    ADD COLUMN IF NOT EXISTS `headcount` UInt64 AFTER `avg_meme_depth`,
+    ADD COLUMN IF NOT EXISTS `group_count` UInt32 AFTER `headcount`;
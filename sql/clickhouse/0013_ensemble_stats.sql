@@ -0,0 +1,17 @@
+CREATE TABLE tribesim.simulation_ensemble_stats
+(
+    `ensemble_id` String,
+    `year` UInt32,
+    `statistic` LowCardinality(String),
+    `replicates` UInt32,
+    `mean` Float64,
+    `sd` Float64,
+    `lower` Float64,
+    `upper` Float64,
+    `extinct_fraction` Float64,
+    `event_time` DateTime DEFAULT now()
+)
+ENGINE = MergeTree
+PARTITION BY ensemble_id
+ORDER BY (ensemble_id, statistic, year)
+SETTINGS index_granularity = 8192;
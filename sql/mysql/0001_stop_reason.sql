@@ -0,0 +1,3 @@
+ALTER TABLE simulation_runs
+  ADD COLUMN stop_reason VARCHAR(32) NULL,
+  ADD COLUMN last_year INT UNSIGNED NULL;
@@ -0,0 +1,22 @@
+CREATE TABLE simulation_ensembles (
+  id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+
+  ensemble_uuid VARCHAR(36) NOT NULL,
+
+  replicates INT UNSIGNED NOT NULL,
+
+  base_seed BIGINT UNSIGNED NOT NULL,
+
+  config JSON NOT NULL,
+
+  extinct_fraction DOUBLE NULL,
+
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+
+  INDEX (ensemble_uuid)
+);
+
+ALTER TABLE simulation_runs
+  ADD COLUMN ensemble_uuid VARCHAR(36) NULL,
+  ADD COLUMN replicate INT UNSIGNED NULL,
+  ADD INDEX (ensemble_uuid);
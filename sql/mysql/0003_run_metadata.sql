@@ -0,0 +1,9 @@
+ALTER TABLE simulation_runs
+  ADD COLUMN state ENUM('running', 'completed', 'extinct', 'cancelled', 'failed') NULL,
+  ADD COLUMN started_at TIMESTAMP NULL,
+  ADD COLUMN finished_at TIMESTAMP NULL,
+  ADD COLUMN seed BIGINT UNSIGNED NULL,
+  ADD COLUMN code_version VARCHAR(64) NULL,
+  ADD COLUMN host VARCHAR(255) NULL,
+  ADD COLUMN summary JSON NULL,
+  ADD INDEX (state);
@@ -0,0 +1,2 @@
+ALTER TABLE simulation_ensembles
+  ADD COLUMN failed_replicates INT UNSIGNED NULL;
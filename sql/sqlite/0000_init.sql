@@ -0,0 +1,80 @@
+CREATE TABLE IF NOT EXISTS simulation_runs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+  run_uuid TEXT NOT NULL,
+
+  config TEXT NOT NULL,
+
+  created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+
+  stop_reason TEXT NULL,
+  last_year INTEGER NULL,
+  ensemble_uuid TEXT NULL,
+  replicate INTEGER NULL,
+  state TEXT NULL CHECK (state IN ('running', 'completed', 'extinct', 'cancelled', 'failed')),
+  started_at TEXT NULL,
+  finished_at TEXT NULL,
+  seed INTEGER NULL,
+  code_version TEXT NULL,
+  host TEXT NULL,
+  summary TEXT NULL
+);
+
+CREATE INDEX IF NOT EXISTS simulation_runs_run_uuid ON simulation_runs (run_uuid);
+CREATE INDEX IF NOT EXISTS simulation_runs_ensemble_uuid ON simulation_runs (ensemble_uuid);
+CREATE INDEX IF NOT EXISTS simulation_runs_state ON simulation_runs (state);
+
+CREATE TABLE IF NOT EXISTS simulation_ensembles (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+  ensemble_uuid TEXT NOT NULL,
+
+  replicates INTEGER NOT NULL,
+
+  base_seed INTEGER NOT NULL,
+
+  config TEXT NOT NULL,
+
+  extinct_fraction REAL NULL,
+
+  created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS simulation_ensembles_ensemble_uuid ON simulation_ensembles (ensemble_uuid);
+
+CREATE TABLE IF NOT EXISTS simulation_yearly_global_stats (
+  simulation_id TEXT NOT NULL,
+  year INTEGER NOT NULL,
+  total_memes_known INTEGER NOT NULL,
+  avg_memes_known REAL NOT NULL,
+  avg_trick_efficiency REAL NOT NULL,
+  avg_brain_volume REAL NOT NULL,
+  avg_meme_size REAL NOT NULL,
+  resources REAL NOT NULL,
+  shock INTEGER NOT NULL,
+  max_meme_depth INTEGER NOT NULL,
+  avg_meme_depth REAL NOT NULL,
+  headcount INTEGER NOT NULL,
+  group_count INTEGER NOT NULL,
+  event_time TEXT DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (simulation_id, year)
+);
+
+CREATE TABLE IF NOT EXISTS simulation_yearly_meme_stats (
+  simulation_id TEXT NOT NULL,
+  year INTEGER NOT NULL,
+  meme_kind TEXT NOT NULL,
+  avg_meme_efficiency REAL NOT NULL,
+  avg_meme_size REAL NOT NULL,
+  copies INTEGER NOT NULL,
+  distinct_memes INTEGER NOT NULL,
+  carrier_fraction REAL NOT NULL,
+  inventions INTEGER NOT NULL,
+  transfers INTEGER NOT NULL,
+  vertical_transfers INTEGER NOT NULL,
+  losses INTEGER NOT NULL,
+  copy_errors INTEGER NOT NULL,
+  max_depth INTEGER NOT NULL,
+  event_time TEXT DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (simulation_id, year, meme_kind)
+);
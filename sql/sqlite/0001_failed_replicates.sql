@@ -0,0 +1 @@
+ALTER TABLE simulation_ensembles ADD COLUMN failed_replicates INTEGER NULL;
@@ -1,6 +1,8 @@
 extern crate rocket;
 use crate::config::config::*;
 use crate::config::file::*;
+use crate::runtime::cancel::*;
+use crate::runtime::ensemble::*;
 use crate::runtime::run_sim::*;
 
 use crate::api::model::*;
@@ -27,7 +29,7 @@ async fn run_default_sim() -> Result<Json<RunSimResponse>, Status> {
     spawn_sim(run_uuid.clone(), boilerplate_cfg);
 
     let response = RunSimResponse {
-        status: format!("ok"),
+        status: "ok".to_string(),
         error: ResponseError::ok(),
         sim_uuid: run_uuid.clone(),
     };
@@ -35,7 +37,52 @@ async fn run_default_sim() -> Result<Json<RunSimResponse>, Status> {
     Ok(Json(response))
 }
 
+#[get("/run_default_ensemble?<replicates>")]
+async fn run_default_ensemble(replicates: usize) -> Result<Json<RunEnsembleResponse>, Status> {
+    if replicates == 0 {
+        return Err(Status::BadRequest);
+    }
+    let config_path = env::var("SIM_CONFIG").expect("SIM_CONFIG must be set");
+    let boilerplate_cfg =
+        load_config_from_json(config_path.as_str()).map_err(|_| Status::new(500))?;
+    let ensemble_uuid = generate_uuid();
+
+    let uuid = ensemble_uuid.clone();
+    task::spawn(async move { initiate_ensemble(uuid, boilerplate_cfg, replicates).await });
+
+    let response = RunEnsembleResponse {
+        status: "ok".to_string(),
+        error: ResponseError::ok(),
+        ensemble_uuid,
+    };
+
+    Ok(Json(response))
+}
+
+/// Cancels a run or an ensemble started by this server and still in progress.
+#[get("/cancel/<uuid>")]
+async fn cancel(uuid: String) -> Json<CancelResponse> {
+    if !request_cancel(&uuid) {
+        return Json(CancelResponse {
+            status: "error".to_string(),
+            error: ResponseError {
+                code: 404,
+                message: format!("No run or ensemble {} is in progress", uuid),
+            },
+            uuid,
+        });
+    }
+    Json(CancelResponse {
+        status: "ok".to_string(),
+        error: ResponseError::ok(),
+        uuid,
+    })
+}
+
 #[launch]
 pub fn start_api_server() -> _ {
-    rocket::build().mount("/api/v1/", routes![run_default_sim])
+    rocket::build().mount(
+        "/api/v1/",
+        routes![run_default_sim, run_default_ensemble, cancel],
+    )
 }
@@ -13,6 +13,20 @@ pub struct RunSimResponse {
     pub sim_uuid: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RunEnsembleResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub ensemble_uuid: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CancelResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub uuid: String,
+}
+
 impl ResponseError {
     pub fn ok() -> Self {
         Self {
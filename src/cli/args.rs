@@ -1,9 +1,44 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Run in web server mode (default - false)
     #[arg(short, long, default_value_t = false)]
     pub launch_server: bool,
+
+    /// Run an ensemble of this many replicates of the configuration (default - 1)
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub replicates: usize,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Apply the pending schema migrations of the configured backend
+    /// (ClickHouse and MySQL, or SQLite when SQLITE_DB is set)
+    Migrate {
+        /// Record the ClickHouse migrations up to this version as applied
+        /// without running them, for a database set up by hand
+        #[arg(long)]
+        clickhouse_baseline: Option<u32>,
+        /// Record the MySQL migrations up to this version as applied
+        /// without running them, for a database set up by hand
+        #[arg(long)]
+        mysql_baseline: Option<u32>,
+    },
+    /// Export the global, meme and group statistics of a run to Parquet files
+    Export {
+        /// Run to export
+        run_uuid: String,
+        /// Directory the files are written to, partitioned by ensemble and run
+        dir: String,
+    },
 }
@@ -2,13 +2,38 @@ use rand::Rng;
 use rand_distr::{Distribution, Normal};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub enum MemeType {
+use crate::config::expression::Expression;
+
+/// The agent trait a meme kind adds its effect to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectTarget {
+    /// Hunting efficiency.
     Hunting,
+    /// Learning efficiency.
     Learning,
+    /// Teaching efficiency.
     Teaching,
+    /// Share of the group resources taken by the agent.
     Trick,
+    /// Yearly probability to waste a unit of resources.
     Useless,
+    /// Fighting strength in conflicts.
+    Warfare,
+    /// Reduction of the yearly food cost.
+    ResourceCost,
+    /// Relative reduction of the age-dependent death probability.
+    Mortality,
+    /// Reduction of the resource cost of offspring, as `cost / (1 + effect)`.
+    Fertility,
+    /// No effect besides taking up memory.
+    None,
+}
+
+/// A cultural trait declared in the configuration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemeKindCfg {
+    pub name: String,
+    pub target: EffectTarget,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -31,9 +56,37 @@ pub struct MutationCfg {
     pub teaching_mutation: MutationParams,
 }
 
+/// Trait by which members are sorted in a trait-assorted fission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssortTrait {
+    BrainVolume,
+    MemesKnown,
+}
+
+/// How an oversized group splits.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FissionPolicy {
+    /// Every member joins one of two halves by a coin flip.
+    #[default]
+    Random,
+    /// Full siblings stay together; sibships are dealt to the two halves so
+    /// that their sizes stay balanced.
+    KinCohesive,
+    /// Members below the median of the trait form one group, the rest the other.
+    TraitAssorted { by: AssortTrait },
+    /// A random `fraction` of the members leaves, the rest stays. The
+    /// fraction lies strictly between 0 and 1.
+    Splinter { fraction: f64 },
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GroupCfg {
     pub max_size: usize,
+    #[serde(default)]
+    pub fission: FissionPolicy,
+    /// Groups smaller than this fuse with another group; 0 disables fusion.
+    #[serde(default)]
+    pub min_size: usize,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -42,27 +95,439 @@ pub struct Range {
     pub max: f64,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemeConfig {
-    pub meme_kind: MemeType,
+    /// Name of one of the declared `meme_kinds`.
+    pub meme_kind: String,
     pub probability: f64,
     pub size: Range,
     pub effect: Range,
     pub size_effect_corr: f64,
+    /// Probability that an invention improves a meme of the same kind the
+    /// inventor already knows instead of being a new independent meme.
+    #[serde(default)]
+    pub improvement_probability: f64,
+    /// Effect of an improvement relative to the meme it improves.
+    #[serde(default = "default_improvement_factor")]
+    pub improvement_factor: Range,
+    /// Range of the complexity drawn for each new meme.
+    #[serde(default = "default_complexity")]
+    pub complexity: Range,
+}
+
+/// Death probability of agents aged `min_age` and older (up to the next band).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AgeBand {
+    pub min_age: u8,
+    pub probability: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MortalityModel {
+    /// `death_prob_multiplier * age`, with the multiplier taken from `agent_config`.
+    Linear,
+    /// `a * e^(b * age)`
+    Gompertz { a: f64, b: f64 },
+    /// `c + a * e^(b * age)`
+    GompertzMakeham { a: f64, b: f64, c: f64 },
+    /// Constant probability per age band. Bands are looked up by `min_age`;
+    /// ages below the first band never die of old age.
+    Piecewise { table: Vec<AgeBand> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MortalityCfg {
+    pub model: MortalityModel,
+    /// Number of consecutive years without food an agent dies after.
+    pub starvation_years: u8,
+}
+
+/// How an agent picks the partner it learns from or teaches.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PartnerSelection {
+    Uniform,
+    /// Weighted by `tot_hunting_efficiency ^ bias`.
+    PrestigeHunting,
+    /// Weighted by `(1 + trick_efficiency) ^ bias`.
+    PrestigeTrick,
+    /// Weighted by `(1 + age) ^ bias`, favouring elders.
+    AgeBiased,
+    /// Parents, children and siblings get weight `1 + bias`, others 1.
+    KinBiased,
+}
+
+/// Which of the partner's memes is offered for transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MemeChoice {
+    /// Random memes are drawn until an unknown one is found or the
+    /// attempts run out.
+    Random,
+    /// The most effective meme the student does not know yet.
+    MostEffective,
+}
+
+/// Which meme property makes a meme hard to transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DifficultySource {
+    Size,
+    Complexity,
+}
+
+/// How meme difficulty `d` combines with the efficiency-based success
+/// probability `p` of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DifficultyModel {
+    /// Success does not depend on the meme.
+    None,
+    /// `p / (1 + weight * d)`
+    Ratio { weight: f64 },
+    /// `1 / (1 + e^(-steepness * (p - weight * d)))`
+    Logistic { steepness: f64, weight: f64 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyCfg {
+    pub source: DifficultySource,
+    pub model: DifficultyModel,
+}
+
+/// Transmission noise: with `probability` a learned copy becomes a new meme
+/// whose effect and size are perturbed by normal noise.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyErrorCfg {
+    pub probability: f64,
+    pub effect_std: f64,
+    pub size_std: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferCfg {
+    /// Interactions per agent in each transfer phase.
+    pub rounds: usize,
+    /// Memes drawn per interaction when `meme_choice` is `Random`.
+    pub meme_attempts: usize,
+    pub partner_selection: PartnerSelection,
+    /// Strength of the partner selection bias; 0 means uniform.
+    pub bias: f64,
+    pub meme_choice: MemeChoice,
+    /// Multiplier of the success probability, e.g. to weigh horizontal
+    /// transfer against vertical transmission; 0 disables it.
+    pub success_weight: f64,
+    /// Copying errors of both horizontal and vertical transfer.
+    pub copy_error: CopyErrorCfg,
+    /// Dependence of the success of both horizontal and vertical transfer
+    /// on the meme.
+    pub difficulty: DifficultyCfg,
+}
+
+/// How the success probability of parent-to-offspring transmission is built.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum VerticalSuccess {
+    /// The per-kind probability alone.
+    Fixed,
+    /// The per-kind probability times the child's learning efficiency plus
+    /// the parent's teaching efficiency, as in the teaching phase.
+    Efficiency,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KindProbability {
+    pub meme_kind: String,
+    pub probability: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerticalTransferCfg {
+    pub enabled: bool,
+    /// Memes each parent tries to pass on to a newborn.
+    pub attempts: usize,
+    pub success: VerticalSuccess,
+    /// Per-kind probabilities; kinds not listed use `default_probability`.
+    pub probabilities: Vec<KindProbability>,
+    pub default_probability: f64,
+}
+
+/// Stop once the least-squares slope of the mean brain volume over the last
+/// `window` years is below `epsilon` in absolute value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlateauCfg {
+    pub window: usize,
+    pub epsilon: f64,
+}
+
+/// Conditions ending a run before `epoch` years; all are optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StopCfg {
+    pub min_headcount: Option<u64>,
+    pub max_headcount: Option<u64>,
+    pub plateau: Option<PlateauCfg>,
+    pub wall_clock_seconds: Option<u64>,
+    /// Expression over the global statistics, e.g. `avg_memes_known > 20`.
+    pub expression: Option<String>,
+}
+
+/// Distribution an initial value is drawn from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ValueDistribution {
+    Fixed { value: f64 },
+    Normal { mean: f64, std: f64 },
+    Uniform { min: f64, max: f64 },
+}
+
+/// Founders of a run. Alleles are drawn independently for each of the two
+/// copies and clamped like mutated ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PopulationCfg {
+    /// Sizes of the founder groups; when empty, three groups of
+    /// `resources / 51` members are created.
+    pub group_sizes: Vec<usize>,
+    pub mc_allele: ValueDistribution,
+    pub le_allele: ValueDistribution,
+    pub te_allele: ValueDistribution,
+    /// Random memes each founder tries to learn.
+    pub starting_memes: usize,
+    pub resources: ValueDistribution,
+    pub age: ValueDistribution,
+    /// Load the founders from this snapshot file instead.
+    pub snapshot: Option<String>,
+}
+
+/// How emigrants weigh the possible destination groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MigrationDestination {
+    Uniform,
+    /// Proportionally to the number of members.
+    GroupSize,
+    /// Proportionally to the mean resources of the members.
+    ResourcesPerCapita,
+}
+
+/// Yearly emigration probability of an agent is
+/// `rate * (1 + age_slope * age) * sex factor`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MigrationCfg {
+    pub rate: f64,
+    pub age_slope: f64,
+    pub female_factor: f64,
+    pub male_factor: f64,
+    pub destination: MigrationDestination,
+    /// Fraction of their resources migrants lose on the way.
+    pub resource_cost: f64,
+    /// Probability for each meme of a migrant to be forgotten on the way.
+    pub meme_loss_probability: f64,
+}
+
+/// How a group's resources are split between its members.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SharingRule {
+    /// Proportionally to `1 + trick_efficiency`.
+    TrickWeighted,
+    /// Equal shares.
+    Egalitarian,
+    /// Trick-weighted, after which every member passes `kin_fraction` of its
+    /// share on to its relatives in the group, proportionally to relatedness.
+    KinWeighted { kin_fraction: f64 },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharingCfg {
+    pub rule: SharingRule,
+    /// Children younger than this are provisioned by their parents.
+    pub provisioning_years: u8,
+    /// Fraction of a parent's resources split between its provisioned children.
+    pub provisioning_fraction: f64,
+    /// Probability that a member who could afford the hunt, and is not
+    /// provisioned any more, skips it and keeps the hunting cost.
+    pub shirking_probability: f64,
+    /// Members who skipped the hunt this year have their share weight
+    /// reduced by this fraction.
+    pub cheater_penalty: f64,
+}
+
+/// What females look for in a mate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MateChoice {
+    Random,
+    /// Weight `(1 + trick_efficiency)^strength`.
+    Trick,
+    /// Weight `(1 + resources)^strength`.
+    Resources,
+    /// Weight `(1 + |brain volume difference|)^-strength`.
+    SimilarBrain,
+}
+
+/// Pairing of agents for reproduction. Without sexes eligible agents are
+/// paired uniformly at random and the other options have no effect.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatingCfg {
+    pub sexes: bool,
+    pub choice: MateChoice,
+    pub strength: f64,
+    /// Females a male can mate with in one year; values above 1 allow polygyny.
+    pub max_mates: usize,
+}
+
+/// What an agent does when a new meme does not fit into its memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryPolicy {
+    /// The new meme is not learned.
+    Reject,
+    /// Memes with the smallest effect are forgotten until the new one fits.
+    ForgetLeastEffective,
+    /// Memes are forgotten in the order they were acquired.
+    ForgetOldest,
+    /// Randomly chosen memes are forgotten until the new one fits.
+    ForgetRandom,
+}
+
+/// Memory management: the policy for a full memory and the yearly
+/// forgetting rate, `rate(kind) * (1 + age_slope * age)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryCfg {
+    pub policy: MemoryPolicy,
+    /// Per-kind forgetting rates; kinds not listed use `base_rate`.
+    pub forgetting_rates: Vec<KindProbability>,
+    pub base_rate: f64,
+    pub age_slope: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LandscapeCfg {
+    pub width: usize,
+    pub height: usize,
+    /// Patch richness is drawn uniformly from `[1 - v, 1 + v]` and the yearly
+    /// resources are split between patches proportionally to it.
+    pub richness_variation: f64,
+    /// Groups within this distance of a patch compete for its resources.
+    pub competition_radius: usize,
+    /// Migration destinations are weighted by `e^(-decay * distance)`; the
+    /// remaining weight makes the agent stay.
+    pub migration_decay: f64,
+    /// Place all founder groups on the central patch instead of random ones.
+    pub clustered_start: bool,
+}
+
+/// Total resources from `from_year` on, until the next point of the schedule.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SchedulePoint {
+    pub from_year: usize,
+    pub resources: f64,
+}
+
+/// Multiplies resources by `1 + amplitude * sin(2 * pi * year / period + phase)`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CycleCfg {
+    pub amplitude: f64,
+    pub period: f64,
+    pub phase: f64,
+}
+
+/// Multiplies resources by `1 + x`, where `x(t) = phi * x(t - 1) + N(0, sigma)`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NoiseCfg {
+    pub phi: f64,
+    pub sigma: f64,
+}
+
+/// Every year a shock starts with `probability`, removing `severity` (0..1)
+/// of the resources for `duration` years.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ShockCfg {
+    pub probability: f64,
+    pub severity: f64,
+    pub duration: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentCfg {
+    /// Piecewise schedule of the base resources; `resources` is used
+    /// before the first point.
+    pub schedule: Vec<SchedulePoint>,
+    pub cycle: Option<CycleCfg>,
+    pub noise: Option<NoiseCfg>,
+    pub shocks: Option<ShockCfg>,
+}
+
+/// Fighting strength of a group is
+/// `size_weight * members + warfare_weight * sum(warfare_efficiency)
+/// + hunting_weight * sum(tot_hunting_efficiency)`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConflictCfg {
+    /// Probability that a group raids another group in a given year.
+    pub raid_probability: f64,
+    pub size_weight: f64,
+    pub warfare_weight: f64,
+    pub hunting_weight: f64,
+    /// Probability for each member of the losing group to be killed.
+    pub casualty_rate: f64,
+    /// Fraction of the losers' resources taken by the winners.
+    pub loot_fraction: f64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SimConfig {
     pub mutation_config: MutationCfg,
     pub agent_config: AgentCfg,
+    #[serde(default = "default_meme_kinds")]
+    pub meme_kinds: Vec<MemeKindCfg>,
     pub meme_config: Vec<MemeConfig>,
     pub group_config: GroupCfg,
     pub epoch: usize,
     pub resources: f64,
+    #[serde(default)]
+    pub mortality_config: MortalityCfg,
+    #[serde(default)]
+    pub transfer_config: TransferCfg,
+    #[serde(default)]
+    pub vertical_transfer_config: VerticalTransferCfg,
+    #[serde(default)]
+    pub landscape_config: Option<LandscapeCfg>,
+    #[serde(default)]
+    pub environment_config: EnvironmentCfg,
+    #[serde(default)]
+    pub conflict_config: Option<ConflictCfg>,
+    #[serde(default)]
+    pub memory_config: MemoryCfg,
+    #[serde(default)]
+    pub mating_config: MatingCfg,
+    #[serde(default)]
+    pub sharing_config: SharingCfg,
+    #[serde(default)]
+    pub migration_config: MigrationCfg,
+    #[serde(default)]
+    pub population_config: PopulationCfg,
+    #[serde(default)]
+    pub stop_config: StopCfg,
+    /// Seed of the random number generator; a random one is used if missing.
+    /// Replicates of an ensemble derive their seeds from it.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Path the population at the end of the run is saved to, to be used
+    /// as a snapshot by later runs.
+    #[serde(default)]
+    pub final_snapshot: Option<String>,
+    /// Directory the global, meme and group statistics are also written to
+    /// as Parquet files.
+    #[serde(default)]
+    pub parquet_dir: Option<String>,
 }
 
-impl AgentCfg {
-    pub fn default() -> Self {
+impl Default for AgentCfg {
+    fn default() -> Self {
         Self {
             base_brain_volume: 20.0,
             mem_cost: 1.0,
@@ -82,3 +547,472 @@ impl MutationParams {
         }
     }
 }
+
+impl Default for MortalityCfg {
+    fn default() -> Self {
+        Self {
+            model: MortalityModel::Linear,
+            starvation_years: 2,
+        }
+    }
+}
+
+impl Default for TransferCfg {
+    fn default() -> Self {
+        Self {
+            rounds: 1,
+            meme_attempts: 3,
+            partner_selection: PartnerSelection::Uniform,
+            bias: 1.0,
+            meme_choice: MemeChoice::Random,
+            success_weight: 1.0,
+            copy_error: CopyErrorCfg::default(),
+            difficulty: DifficultyCfg::default(),
+        }
+    }
+}
+
+impl Default for DifficultyCfg {
+    fn default() -> Self {
+        Self {
+            source: DifficultySource::Complexity,
+            model: DifficultyModel::None,
+        }
+    }
+}
+
+impl DifficultyCfg {
+    /// Success probability of transferring a meme of the given size and
+    /// complexity when the agents' efficiencies alone give `p`.
+    pub fn success_probability(&self, p: f64, size: f64, complexity: f64) -> f64 {
+        let d = match self.source {
+            DifficultySource::Size => size,
+            DifficultySource::Complexity => complexity,
+        };
+        match self.model {
+            DifficultyModel::None => p,
+            DifficultyModel::Ratio { weight } => p / (1.0 + weight * d),
+            DifficultyModel::Logistic { steepness, weight } => {
+                1.0 / (1.0 + (-steepness * (p - weight * d)).exp())
+            }
+        }
+    }
+}
+
+impl Default for VerticalTransferCfg {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            attempts: 3,
+            success: VerticalSuccess::Efficiency,
+            probabilities: Vec::new(),
+            default_probability: 1.0,
+        }
+    }
+}
+
+impl Default for PlateauCfg {
+    fn default() -> Self {
+        Self {
+            window: 2000,
+            epsilon: 1e-5,
+        }
+    }
+}
+
+impl Default for PopulationCfg {
+    fn default() -> Self {
+        Self {
+            group_sizes: Vec::new(),
+            mc_allele: ValueDistribution::Fixed { value: 0.0 },
+            le_allele: ValueDistribution::Fixed { value: 0.1 },
+            te_allele: ValueDistribution::Fixed { value: 0.0 },
+            starting_memes: 0,
+            resources: ValueDistribution::Fixed { value: 10.0 },
+            age: ValueDistribution::Fixed { value: 0.0 },
+            snapshot: None,
+        }
+    }
+}
+
+impl Default for MigrationCfg {
+    fn default() -> Self {
+        Self {
+            rate: 0.001,
+            age_slope: 0.0,
+            female_factor: 1.0,
+            male_factor: 1.0,
+            destination: MigrationDestination::Uniform,
+            resource_cost: 0.0,
+            meme_loss_probability: 0.0,
+        }
+    }
+}
+
+impl Default for SharingCfg {
+    fn default() -> Self {
+        Self {
+            rule: SharingRule::TrickWeighted,
+            provisioning_years: 0,
+            provisioning_fraction: 0.0,
+            shirking_probability: 0.0,
+            cheater_penalty: 0.0,
+        }
+    }
+}
+
+impl Default for MatingCfg {
+    fn default() -> Self {
+        Self {
+            sexes: false,
+            choice: MateChoice::Random,
+            strength: 1.0,
+            max_mates: 1,
+        }
+    }
+}
+
+impl Default for MemoryCfg {
+    fn default() -> Self {
+        Self {
+            policy: MemoryPolicy::Reject,
+            forgetting_rates: Vec::new(),
+            base_rate: 0.02,
+            age_slope: 0.0,
+        }
+    }
+}
+
+impl Default for LandscapeCfg {
+    fn default() -> Self {
+        Self {
+            width: 5,
+            height: 5,
+            richness_variation: 0.0,
+            competition_radius: 1,
+            migration_decay: 1.0,
+            clustered_start: false,
+        }
+    }
+}
+
+impl Default for ConflictCfg {
+    fn default() -> Self {
+        Self {
+            raid_probability: 0.05,
+            size_weight: 1.0,
+            warfare_weight: 1.0,
+            hunting_weight: 0.0,
+            casualty_rate: 0.1,
+            loot_fraction: 0.5,
+        }
+    }
+}
+
+impl VerticalTransferCfg {
+    pub fn probability(&self, kind: &str) -> f64 {
+        self.probabilities
+            .iter()
+            .find(|p| p.meme_kind == kind)
+            .map_or(self.default_probability, |p| p.probability)
+    }
+}
+
+impl ValueDistribution {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match *self {
+            ValueDistribution::Fixed { value } => value,
+            ValueDistribution::Normal { mean, std } => Normal::new(mean, std)
+                .expect("Invalid normal parameters")
+                .sample(rng),
+            ValueDistribution::Uniform { min, max } if max > min => rng.gen_range(min..max),
+            ValueDistribution::Uniform { min, .. } => min,
+        }
+    }
+}
+
+impl MemoryCfg {
+    pub fn forgetting_rate(&self, kind: &str) -> f64 {
+        self.forgetting_rates
+            .iter()
+            .find(|r| r.meme_kind == kind)
+            .map_or(self.base_rate, |r| r.probability)
+    }
+}
+
+impl MortalityModel {
+    /// Probability that an agent of the given age dies of old age this year.
+    pub fn death_probability(&self, age: u8, agent_cfg: &AgentCfg) -> f64 {
+        let age = age as f64;
+        match self {
+            MortalityModel::Linear => agent_cfg.death_prob_multiplier * age,
+            MortalityModel::Gompertz { a, b } => a * (b * age).exp(),
+            MortalityModel::GompertzMakeham { a, b, c } => c + a * (b * age).exp(),
+            MortalityModel::Piecewise { table } => table
+                .iter()
+                .filter(|band| band.min_age as f64 <= age)
+                .max_by_key(|band| band.min_age)
+                .map_or(0.0, |band| band.probability),
+        }
+    }
+}
+
+fn default_complexity() -> Range {
+    Range { min: 0.0, max: 0.0 }
+}
+
+fn default_improvement_factor() -> Range {
+    Range { min: 1.1, max: 1.5 }
+}
+
+/// The kinds available when the configuration does not declare any: one
+/// per effect target of the original model, named after it.
+pub fn default_meme_kinds() -> Vec<MemeKindCfg> {
+    [
+        EffectTarget::Hunting,
+        EffectTarget::Learning,
+        EffectTarget::Teaching,
+        EffectTarget::Trick,
+        EffectTarget::Useless,
+        EffectTarget::Warfare,
+    ]
+    .iter()
+    .map(|&target| MemeKindCfg {
+        name: format!("{:?}", target),
+        target,
+    })
+    .collect()
+}
+
+impl SimConfig {
+    /// Index of the meme kind with the given name.
+    pub fn meme_kind_index(&self, name: &str) -> Option<usize> {
+        self.meme_kinds.iter().position(|kind| kind.name == name)
+    }
+
+    /// Checks that every meme kind referred to elsewhere is declared.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, kind) in self.meme_kinds.iter().enumerate() {
+            if self.meme_kinds[..i].iter().any(|k| k.name == kind.name) {
+                return Err(format!("Meme kind {} is declared twice", kind.name));
+            }
+        }
+        let referenced = self.meme_config.iter().map(|c| &c.meme_kind).chain(
+            self.vertical_transfer_config
+                .probabilities
+                .iter()
+                .map(|p| &p.meme_kind)
+                .chain(
+                    self.memory_config
+                        .forgetting_rates
+                        .iter()
+                        .map(|r| &r.meme_kind),
+                ),
+        );
+        for name in referenced {
+            if self.meme_kind_index(name).is_none() {
+                return Err(format!("Unknown meme kind {}", name));
+            }
+        }
+        if let Some(expression) = &self.stop_config.expression {
+            Expression::parse(expression).map_err(|e| format!("Invalid stop expression: {}", e))?;
+        }
+        if let Some(plateau) = &self.stop_config.plateau {
+            if plateau.window < 2 {
+                return Err(format!(
+                    "Invalid plateau window {}, it must span at least 2 years",
+                    plateau.window
+                ));
+            }
+            if plateau.epsilon.is_nan() || plateau.epsilon <= 0.0 {
+                return Err(format!(
+                    "Invalid plateau epsilon {}, it must be positive",
+                    plateau.epsilon
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) = (
+            self.stop_config.min_headcount,
+            self.stop_config.max_headcount,
+        ) {
+            if min > max {
+                return Err(format!(
+                    "min_headcount {} exceeds max_headcount {}",
+                    min, max
+                ));
+            }
+        }
+        let check_std = |name: &str, std: f64| {
+            if std.is_nan() || std < 0.0 {
+                Err(format!("Invalid standard deviation {} of {}", std, name))
+            } else {
+                Ok(())
+            }
+        };
+        let population = &self.population_config;
+        for (name, distribution) in [
+            ("population_config.mc_allele", population.mc_allele),
+            ("population_config.le_allele", population.le_allele),
+            ("population_config.te_allele", population.te_allele),
+            ("population_config.resources", population.resources),
+            ("population_config.age", population.age),
+        ] {
+            if let ValueDistribution::Normal { std, .. } = distribution {
+                check_std(name, std)?;
+            }
+        }
+        if let Some(noise) = self.environment_config.noise {
+            check_std("environment_config.noise.sigma", noise.sigma)?;
+        }
+        let copy_error = self.transfer_config.copy_error;
+        check_std(
+            "transfer_config.copy_error.effect_std",
+            copy_error.effect_std,
+        )?;
+        check_std("transfer_config.copy_error.size_std", copy_error.size_std)?;
+        if self.mortality_config.starvation_years == 0 {
+            return Err("mortality_config.starvation_years must be at least 1".to_string());
+        }
+        if self.mating_config.max_mates == 0 {
+            return Err("mating_config.max_mates must be at least 1".to_string());
+        }
+        if let FissionPolicy::Splinter { fraction } = self.group_config.fission {
+            if !(fraction > 0.0 && fraction < 1.0) {
+                return Err(format!(
+                    "Invalid splinter fraction {}, it must be between 0 and 1",
+                    fraction
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn death_probability_of_models() {
+        let agent_cfg = AgentCfg {
+            death_prob_multiplier: 0.01,
+            ..AgentCfg::default()
+        };
+        let table = vec![
+            AgeBand {
+                min_age: 40,
+                probability: 0.1,
+            },
+            AgeBand {
+                min_age: 15,
+                probability: 0.02,
+            },
+        ];
+        let cases = [
+            (MortalityModel::Linear, 0, 0.0),
+            (MortalityModel::Linear, 30, 0.3),
+            (MortalityModel::Gompertz { a: 0.001, b: 0.1 }, 0, 0.001),
+            (
+                MortalityModel::Gompertz { a: 0.001, b: 0.1 },
+                50,
+                0.001 * 5f64.exp(),
+            ),
+            (
+                MortalityModel::GompertzMakeham {
+                    a: 0.001,
+                    b: 0.1,
+                    c: 0.005,
+                },
+                50,
+                0.005 + 0.001 * 5f64.exp(),
+            ),
+            (
+                MortalityModel::Piecewise {
+                    table: table.clone(),
+                },
+                10,
+                0.0,
+            ),
+            (
+                MortalityModel::Piecewise {
+                    table: table.clone(),
+                },
+                15,
+                0.02,
+            ),
+            (
+                MortalityModel::Piecewise {
+                    table: table.clone(),
+                },
+                39,
+                0.02,
+            ),
+            (MortalityModel::Piecewise { table }, 80, 0.1),
+            (MortalityModel::Piecewise { table: Vec::new() }, 80, 0.0),
+        ];
+        for (model, age, expected) in cases {
+            let probability = model.death_probability(age, &agent_cfg);
+            assert!(
+                (probability - expected).abs() < 1e-12,
+                "{:?} at age {}: {} instead of {}",
+                model,
+                age,
+                probability,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn declared_kinds_build_memes_and_are_validated() {
+        use crate::simulation::agent::*;
+        use crate::simulation::memetics::*;
+        use rand_xoshiro::rand_core::SeedableRng;
+        use rand_xoshiro::Xoshiro256PlusPlus;
+
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(21);
+        let mut cfg = crate::config::file::example_config();
+        let kind = |name: &str, target| MemeKindCfg {
+            name: name.to_string(),
+            target,
+        };
+        cfg.meme_kinds = vec![
+            kind("Spear", EffectTarget::Hunting),
+            kind("Bow", EffectTarget::Hunting),
+            kind("Charm", EffectTarget::Trick),
+        ];
+        let hunting = cfg.meme_config[0].clone();
+        let meme_config = |name: &str| MemeConfig {
+            meme_kind: name.to_string(),
+            ..hunting.clone()
+        };
+        cfg.meme_config = vec![meme_config("Spear"), meme_config("Bow")];
+        assert_eq!(cfg.validate(), Ok(()));
+        assert_eq!(cfg.meme_kind_index("Bow"), Some(1));
+
+        // Two kinds with the same target both add to it
+        let spear = Meme::from_config(&cfg, &cfg.meme_config[0], &mut rng).unwrap();
+        let bow = Meme::from_config(&cfg, &cfg.meme_config[1], &mut rng).unwrap();
+        assert_eq!((spear.kind, bow.kind), (0, 1));
+        assert_eq!(bow.target, EffectTarget::Hunting);
+        assert!(Meme::from_config(&cfg, &meme_config("Sling"), &mut rng).is_none());
+        let mut agent = Agent::default(&mut rng, AgentCfg::default());
+        agent.mc_alleles = Alleles {
+            allele1: 10.0,
+            allele2: 10.0,
+        };
+        let before = agent.tot_hunting_efficiency;
+        assert!(agent.try_learning(spear) && agent.try_learning(bow));
+        let gained = agent.tot_hunting_efficiency - before;
+        assert!((gained - spear.effect - bow.effect).abs() < 1e-12);
+
+        cfg.meme_config.push(meme_config("Sling"));
+        assert_eq!(cfg.validate(), Err("Unknown meme kind Sling".to_string()));
+        cfg.meme_config.pop();
+        cfg.meme_kinds.push(kind("Bow", EffectTarget::Warfare));
+        assert_eq!(
+            cfg.validate(),
+            Err("Meme kind Bow is declared twice".to_string())
+        );
+    }
+}
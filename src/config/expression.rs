@@ -0,0 +1,323 @@
+/// Numeric columns of the global statistics, which expressions can refer
+/// to and ensemble summaries cover.
+pub const VARIABLES: [&str; 12] = [
+    "year",
+    "headcount",
+    "group_count",
+    "total_memes_known",
+    "avg_memes_known",
+    "avg_trick_efficiency",
+    "avg_brain_volume",
+    "avg_meme_size",
+    "resources",
+    "shock",
+    "max_meme_depth",
+    "avg_meme_depth",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// An expression over the yearly global statistics, such as
+/// `avg_memes_known > 20 && year >= 5000`. Comparisons and logical
+/// operators yield 1 or 0; any non-zero value counts as true.
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Number(f64),
+    Variable(&'static str),
+    Not(Box<Expression>),
+    Negate(Box<Expression>),
+    Binary(Box<Expression>, BinaryOp, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+const OPERATORS: [&str; 15] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "!", "=", "|",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| format!("Invalid number {}", &rest[..len]))?;
+            tokens.push(Token::Number(number));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            1
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            1
+        } else {
+            // single `=` and `|` are only listed to report them properly
+            match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(&op) if op != "=" && op != "|" => {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                }
+                _ => return Err(format!("Unexpected character {}", c)),
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser; each level handles operators of one precedence.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn binary_level(
+        &mut self,
+        ops: &[(&'static str, BinaryOp)],
+        next: fn(&mut Parser) -> Result<Expression, String>,
+    ) -> Result<Expression, String> {
+        let symbols: Vec<&'static str> = ops.iter().map(|(s, _)| *s).collect();
+        let mut lhs = next(self)?;
+        while let Some(symbol) = self.peek_op(&symbols) {
+            self.pos += 1;
+            let op = ops.iter().find(|(s, _)| *s == symbol).unwrap().1;
+            let rhs = next(self)?;
+            lhs = Expression::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[("||", BinaryOp::Or)], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[("&&", BinaryOp::And)], Parser::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expression, String> {
+        self.binary_level(
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            Parser::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expression, String> {
+        self.binary_level(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Parser::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[("*", BinaryOp::Mul), ("/", BinaryOp::Div)], Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        match self.peek_op(&["!", "-"]) {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expression::Not(Box::new(self.unary()?)))
+            }
+            Some(_) => {
+                self.pos += 1;
+                Ok(Expression::Negate(Box::new(self.unary()?)))
+            }
+            None => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expression::Number(value)),
+            Some(Token::Ident(name)) => VARIABLES
+                .iter()
+                .find(|v| **v == name)
+                .map(|v| Expression::Variable(v))
+                .ok_or_else(|| format!("Unknown variable {}", name)),
+            Some(Token::LParen) => {
+                let inner = self.or()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected token {:?}", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Expression, String> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expression = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected token {:?}", token)),
+        }
+    }
+
+    /// Evaluates the expression, taking the value of each variable from
+    /// `value`. Logical operators treat non-finite operands as false.
+    pub fn evaluate<F: Fn(&str) -> f64>(&self, value: &F) -> f64 {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            Expression::Number(number) => *number,
+            Expression::Variable(name) => value(name),
+            Expression::Not(inner) => truth(!holds(inner.evaluate(value))),
+            Expression::Negate(inner) => -inner.evaluate(value),
+            Expression::Binary(lhs, op, rhs) => {
+                let (a, b) = (lhs.evaluate(value), rhs.evaluate(value));
+                match op {
+                    BinaryOp::Or => truth(holds(a) || holds(b)),
+                    BinaryOp::And => truth(holds(a) && holds(b)),
+                    BinaryOp::Eq => truth(a == b),
+                    BinaryOp::Ne => truth(a != b),
+                    BinaryOp::Lt => truth(a < b),
+                    BinaryOp::Le => truth(a <= b),
+                    BinaryOp::Gt => truth(a > b),
+                    BinaryOp::Ge => truth(a >= b),
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                }
+            }
+        }
+    }
+
+    /// Whether the expression is true, i.e. evaluates to a finite non-zero
+    /// value.
+    pub fn holds<F: Fn(&str) -> f64>(&self, value: &F) -> bool {
+        holds(self.evaluate(value))
+    }
+}
+
+/// Truth value of a number: NaN and infinities, e.g. from a division by
+/// zero, are false like 0.
+fn holds(x: f64) -> bool {
+    x.is_finite() && x != 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(name: &str) -> f64 {
+        match name {
+            "year" => 5000.0,
+            "headcount" => 120.0,
+            "avg_memes_known" => 25.0,
+            "shock" => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    #[test]
+    fn evaluates_valid_expressions() {
+        let cases = [
+            ("42", 42.0),
+            ("1 + 2 * 3", 7.0),
+            ("(1 + 2) * 3", 9.0),
+            ("10 - 4 - 3", 3.0),
+            ("12 / 3 / 2", 2.0),
+            ("-3 + 5", 2.0),
+            ("--3", 3.0),
+            ("2 * -3", -6.0),
+            ("-year", -5000.0),
+            ("!0", 1.0),
+            ("!shock && year > 10", 1.0),
+            ("1 || 0 && 0", 1.0),
+            ("(1 || 0) && 0", 0.0),
+            ("1 + 1 == 2", 1.0),
+            ("year >= 5000 && avg_memes_known > 20", 1.0),
+            ("year < 5000 || headcount != 120", 0.0),
+            ("headcount <= 120", 1.0),
+            ("avg_memes_known / 5 > 4", 1.0),
+            ("!(0 / 0)", 1.0),
+            ("1 / 0 && 1", 0.0),
+            ("0 / 0 || shock", 0.0),
+        ];
+        for (input, expected) in cases {
+            let expression = Expression::parse(input)
+                .unwrap_or_else(|e| panic!("{} did not parse: {}", input, e));
+            assert_eq!(expression.evaluate(&value), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        let cases = [
+            ("year = 5000", "Unexpected character ="),
+            ("shock | 1", "Unexpected character |"),
+            ("brain_size > 1", "Unknown variable brain_size"),
+            ("(year > 1", "Missing closing parenthesis"),
+            ("year > 1)", "Unexpected token RParen"),
+            ("year >", "Unexpected end of expression"),
+            ("", "Unexpected end of expression"),
+            ("1..2", "Invalid number 1..2"),
+            ("year $ 2", "Unexpected character $"),
+        ];
+        for (input, expected) in cases {
+            match Expression::parse(input) {
+                Ok(expression) => panic!("{} parsed as {:?}", input, expression),
+                Err(e) => assert_eq!(e, expected, "{}", input),
+            }
+        }
+    }
+}
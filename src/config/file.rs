@@ -3,17 +3,61 @@ use std::fs::File;
 use std::io::{BufReader, BufWriter};
 
 use crate::config::config::*;
+use crate::simulation::group::*;
 
 pub fn load_config_from_json(path: &str) -> std::io::Result<SimConfig> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
     let config: SimConfig = serde_json::from_reader(reader)?;
+    config
+        .validate()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     Ok(config)
 }
 
+/// The example configuration of the repository, for tests.
+#[cfg(test)]
+pub fn example_config() -> SimConfig {
+    serde_json::from_str(include_str!("../../examples/cfg.json")).expect("valid example config")
+}
+
 pub fn save_config_to_json(path: &str, config: &SimConfig) -> std::io::Result<()> {
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
     serde_json::to_writer_pretty(writer, config)?;
     Ok(())
 }
+
+/// Saves the groups of a running simulation, to be used as founders later.
+pub fn save_snapshot(path: &str, groups: &[Group]) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    let writer = BufWriter::new(file);
+    serde_json::to_writer(writer, groups)?;
+    Ok(())
+}
+
+/// Loads groups saved by `save_snapshot`. Agents and groups take their
+/// configuration from `config`, whose meme kinds must include all kinds
+/// known to the snapshot.
+pub fn load_snapshot(path: &str, config: &SimConfig) -> std::io::Result<Vec<Group>> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+    let mut groups: Vec<Group> = serde_json::from_reader(reader)?;
+    for group in groups.iter_mut() {
+        group.config = config.group_config;
+        for agent in group.members.iter_mut() {
+            agent.config = config.agent_config;
+            if agent
+                .memes
+                .iter()
+                .any(|m| m.kind >= config.meme_kinds.len())
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Snapshot contains memes of undeclared kinds",
+                ));
+            }
+        }
+    }
+    Ok(groups)
+}
@@ -1,2 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod config;
+pub mod expression;
 pub mod file;
@@ -0,0 +1,404 @@
+use crate::config::config::*;
+use crate::db::clickhouse_client::*;
+use crate::db::migrations::*;
+use crate::db::sqlite_client::SqliteDb;
+use crate::db::{mysql_client, sqlite_client};
+
+use mysql_async::Pool;
+use std::env;
+use std::fmt;
+
+/// State of a run as recorded in `simulation_runs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    Completed,
+    Extinct,
+    Cancelled,
+    Failed,
+}
+
+impl fmt::Display for RunState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RunState::Running => "running",
+            RunState::Completed => "completed",
+            RunState::Extinct => "extinct",
+            RunState::Cancelled => "cancelled",
+            RunState::Failed => "failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where runs and their statistics are stored.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// Statistics in ClickHouse, run metadata in MySQL.
+    Remote {
+        clickhouse_url: String,
+        pool: Pool,
+        creds: DBCreds,
+    },
+    /// Run metadata, global and meme statistics in a single SQLite file.
+    /// The other statistics are not kept.
+    Sqlite { path: String, db: SqliteDb },
+}
+
+impl Backend {
+    /// SQLite if `SQLITE_DB` is set, otherwise ClickHouse and MySQL.
+    pub fn from_env() -> Self {
+        if let Ok(path) = env::var("SQLITE_DB") {
+            let db = SqliteDb::open(&path)
+                .unwrap_or_else(|e| panic!("Cannot open SQLite database {}: {}", path, e));
+            return Backend::Sqlite { path, db };
+        }
+        let clickhouse_url = env::var("CLICKHOUSE_URL").expect("CLICKHOUSE_URL must be set");
+        let user = env::var("CLICKHOUSE_USER").expect("CLICKHOUSE_USER must be set");
+        let password = env::var("CLICKHOUSE_PASSWORD").expect("CLICKHOUSE_PASSWORD must be set");
+        let database = env::var("CLICKHOUSE_DB").expect("CLICKHOUSE_DB must be set");
+        let mysql_url = env::var("MYSQL_URL").expect("MYSQL_URL must be set");
+        Backend::Remote {
+            clickhouse_url,
+            pool: Pool::new(mysql_url.as_str()),
+            creds: DBCreds {
+                user,
+                password,
+                database,
+            },
+        }
+    }
+
+    /// Applies the pending migrations and returns the names of the applied ones.
+    pub async fn migrate(
+        &self,
+        baseline: Baseline,
+    ) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                pool,
+                creds,
+            } => {
+                let mut applied =
+                    migrate_clickhouse(clickhouse_url, creds, baseline.clickhouse).await?;
+                applied.extend(migrate_mysql(pool, baseline.mysql).await?);
+                Ok(applied)
+            }
+            Backend::Sqlite { .. } if baseline.clickhouse.is_some() || baseline.mysql.is_some() => {
+                Err("SQLite databases are always migrated by tribesim and need no baseline".into())
+            }
+            Backend::Sqlite { db, .. } => db.run(|conn| Ok(migrate_sqlite(conn)?)).await,
+        }
+    }
+
+    /// Checks that the schema is at the version this build writes. SQLite
+    /// databases are migrated instead, so that a new file needs no setup.
+    pub async fn check_schema(&self) -> Result<(), String> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                pool,
+                creds,
+            } => check_schema_versions(clickhouse_url, creds, pool).await,
+            Backend::Sqlite { path, db } => db
+                .run(|conn| Ok(migrate_sqlite(conn)?))
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Cannot migrate SQLite database {}: {}", path, e)),
+        }
+    }
+
+    pub async fn store_simulation_config(
+        &self,
+        run_uuid: &str,
+        config: &SimConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_simulation_config(pool, run_uuid, config).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let (run_uuid, config) = (run_uuid.to_string(), config.clone());
+                db.run(move |conn| sqlite_client::store_simulation_config(conn, &run_uuid, &config))
+                    .await
+            }
+        }
+    }
+
+    pub async fn load_simulation_config(
+        &self,
+        run_uuid: &str,
+    ) -> Result<SimConfig, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::load_simulation_config(pool, run_uuid).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::load_simulation_config(conn, &run_uuid))
+                    .await
+            }
+        }
+    }
+
+    pub async fn store_run_start(
+        &self,
+        run_uuid: &str,
+        seed: u64,
+        code_version: &str,
+        host: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_run_start(pool, run_uuid, seed, code_version, host).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let (run_uuid, code_version, host) = (
+                    run_uuid.to_string(),
+                    code_version.to_string(),
+                    host.to_string(),
+                );
+                db.run(move |conn| {
+                    sqlite_client::store_run_start(conn, &run_uuid, seed, &code_version, &host)
+                })
+                .await
+            }
+        }
+    }
+
+    pub async fn store_run_progress(
+        &self,
+        run_uuid: &str,
+        last_year: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_run_progress(pool, run_uuid, last_year).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::store_run_progress(conn, &run_uuid, last_year))
+                    .await
+            }
+        }
+    }
+
+    pub async fn store_run_result(
+        &self,
+        run_uuid: &str,
+        state: RunState,
+        stop_reason: &str,
+        last_year: usize,
+        summary: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_run_result(
+                    pool,
+                    run_uuid,
+                    state,
+                    stop_reason,
+                    last_year,
+                    summary,
+                )
+                .await
+            }
+            Backend::Sqlite { db, .. } => {
+                let (run_uuid, stop_reason, summary) = (
+                    run_uuid.to_string(),
+                    stop_reason.to_string(),
+                    summary.to_string(),
+                );
+                db.run(move |conn| {
+                    sqlite_client::store_run_result(
+                        conn,
+                        &run_uuid,
+                        state,
+                        &stop_reason,
+                        last_year,
+                        &summary,
+                    )
+                })
+                .await
+            }
+        }
+    }
+
+    pub async fn store_run_state(
+        &self,
+        run_uuid: &str,
+        state: RunState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_run_state(pool, run_uuid, state).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::store_run_state(conn, &run_uuid, state))
+                    .await
+            }
+        }
+    }
+
+    pub async fn store_replicate_tag(
+        &self,
+        run_uuid: &str,
+        ensemble_uuid: &str,
+        replicate: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_replicate_tag(pool, run_uuid, ensemble_uuid, replicate).await
+            }
+            Backend::Sqlite { db, .. } => {
+                let (run_uuid, ensemble_uuid) = (run_uuid.to_string(), ensemble_uuid.to_string());
+                db.run(move |conn| {
+                    sqlite_client::store_replicate_tag(conn, &run_uuid, &ensemble_uuid, replicate)
+                })
+                .await
+            }
+        }
+    }
+
+    pub async fn store_ensemble(
+        &self,
+        ensemble_uuid: &str,
+        replicates: usize,
+        base_seed: u64,
+        config: &SimConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_ensemble(pool, ensemble_uuid, replicates, base_seed, config)
+                    .await
+            }
+            Backend::Sqlite { db, .. } => {
+                let (ensemble_uuid, config) = (ensemble_uuid.to_string(), config.clone());
+                db.run(move |conn| {
+                    sqlite_client::store_ensemble(
+                        conn,
+                        &ensemble_uuid,
+                        replicates,
+                        base_seed,
+                        &config,
+                    )
+                })
+                .await
+            }
+        }
+    }
+
+    pub async fn store_ensemble_result(
+        &self,
+        ensemble_uuid: &str,
+        extinct_fraction: f64,
+        failed_replicates: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => {
+                mysql_client::store_ensemble_result(
+                    pool,
+                    ensemble_uuid,
+                    extinct_fraction,
+                    failed_replicates,
+                )
+                .await
+            }
+            Backend::Sqlite { db, .. } => {
+                let ensemble_uuid = ensemble_uuid.to_string();
+                db.run(move |conn| {
+                    sqlite_client::store_ensemble_result(
+                        conn,
+                        &ensemble_uuid,
+                        extinct_fraction,
+                        failed_replicates,
+                    )
+                })
+                .await
+            }
+        }
+    }
+
+    /// The ensemble a run belongs to, if any.
+    pub async fn load_ensemble_uuid(
+        &self,
+        run_uuid: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote { pool, .. } => mysql_client::load_ensemble_uuid(pool, run_uuid).await,
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::load_ensemble_uuid(conn, &run_uuid))
+                    .await
+            }
+        }
+    }
+
+    pub async fn load_global_stats(
+        &self,
+        run_uuid: &str,
+    ) -> Result<Vec<GlobalStatsRow>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                creds,
+                ..
+            } => load_global_stats(clickhouse_url, creds, run_uuid).await,
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::load_global_stats(conn, &run_uuid))
+                    .await
+            }
+        }
+    }
+
+    pub async fn load_meme_stats(
+        &self,
+        run_uuid: &str,
+    ) -> Result<Vec<MemeStatsRow>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                creds,
+                ..
+            } => load_meme_stats(clickhouse_url, creds, run_uuid).await,
+            Backend::Sqlite { db, .. } => {
+                let run_uuid = run_uuid.to_string();
+                db.run(move |conn| sqlite_client::load_meme_stats(conn, &run_uuid))
+                    .await
+            }
+        }
+    }
+
+    /// Group statistics of a run; none are kept by SQLite.
+    pub async fn load_group_stats(
+        &self,
+        run_uuid: &str,
+    ) -> Result<Vec<GroupStatsRow>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                creds,
+                ..
+            } => load_group_stats(clickhouse_url, creds, run_uuid).await,
+            Backend::Sqlite { .. } => Ok(Vec::new()),
+        }
+    }
+
+    /// Inserts the yearly statistics of an ensemble; not kept by SQLite.
+    pub async fn insert_ensemble_stats(
+        &self,
+        rows: &[EnsembleStatsRow],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Backend::Remote {
+                clickhouse_url,
+                creds,
+                ..
+            } => insert_ensemble_stats(clickhouse_url, creds, rows).await,
+            Backend::Sqlite { .. } => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use clickhouse::{Client, Row};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// Matches the simulation_yearly_global_stats table
-#[derive(Debug, Row, Serialize, Deserialize)]
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
 pub struct GlobalStatsRow {
     pub simulation_id: String,
     pub year: u32,
@@ -13,9 +14,36 @@ pub struct GlobalStatsRow {
     pub avg_trick_efficiency: f64,
     pub avg_brain_volume: f64,
     pub avg_meme_size: f64,
+    pub resources: f64,
+    pub shock: bool,
+    pub max_meme_depth: u32,
+    pub avg_meme_depth: f64,
+    pub headcount: u64,
+    pub group_count: u32,
     // event_time has DEFAULT now(), so we omit it unless we want to supply it
 }
 
+impl GlobalStatsRow {
+    /// Value of one of the numeric columns listed in `VARIABLES`.
+    pub fn column(&self, name: &str) -> f64 {
+        match name {
+            "year" => self.year as f64,
+            "headcount" => self.headcount as f64,
+            "group_count" => self.group_count as f64,
+            "total_memes_known" => self.total_memes_known as f64,
+            "avg_memes_known" => self.avg_memes_known,
+            "avg_trick_efficiency" => self.avg_trick_efficiency,
+            "avg_brain_volume" => self.avg_brain_volume,
+            "avg_meme_size" => self.avg_meme_size,
+            "resources" => self.resources,
+            "shock" => self.shock as u8 as f64,
+            "max_meme_depth" => self.max_meme_depth as f64,
+            "avg_meme_depth" => self.avg_meme_depth,
+            _ => panic!("Unknown global statistic {}", name),
+        }
+    }
+}
+
 /// Matches the simulation_yearly_meme_stats table
 #[derive(Debug, Row, Serialize, Deserialize)]
 pub struct MemeStatsRow {
@@ -25,6 +53,112 @@ pub struct MemeStatsRow {
 
     pub avg_meme_efficiency: f64,
     pub avg_meme_size: f64,
+    pub copies: u64,
+    pub distinct_memes: u64,
+    pub carrier_fraction: f64,
+    pub inventions: u64,
+    pub transfers: u64,
+    pub vertical_transfers: u64,
+    pub losses: u64,
+    pub copy_errors: u64,
+    pub max_depth: u32,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_yearly_reproduction_stats table
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct ReproductionStatsRow {
+    pub simulation_id: String,
+    pub year: u32,
+
+    pub eligible_agents: u64,
+    pub pairs: u64,
+    pub births: u64,
+    pub sires: u64,
+    pub failed_attempts: u64,
+    pub mean_parental_resources: f64,
+    pub mean_child_cost: f64,
+    pub mean_child_endowment: f64,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_yearly_resource_stats table
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct ResourceStatsRow {
+    pub simulation_id: String,
+    pub year: u32,
+
+    pub total_distributed: f64,
+    pub gini: f64,
+    pub spent_hunting: f64,
+    pub spent_food: f64,
+    pub spent_useless: f64,
+    pub spent_reproduction: f64,
+    pub lost_with_dead: f64,
+    pub lost_with_dissolved: f64,
+    pub lost_in_migration: f64,
+    pub expired: f64,
+    pub total_held: f64,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_yearly_group_stats table
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct GroupStatsRow {
+    pub simulation_id: String,
+    pub year: u32,
+    pub group_id: u64,
+
+    pub patch: Option<u32>,
+    pub headcount: u64,
+    pub resource_share: f64,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_yearly_conflict_stats table
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct ConflictStatsRow {
+    pub simulation_id: String,
+    pub year: u32,
+
+    pub conflicts: u64,
+    pub attacker_wins: u64,
+    pub casualties: u64,
+    pub resources_looted: f64,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_yearly_migration_stats table
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct MigrationStatsRow {
+    pub simulation_id: String,
+    pub year: u32,
+
+    pub migrants: u64,
+    pub mean_migrant_age: f64,
+    pub memes_carried: u64,
+    pub memes_lost: u64,
+    pub resources_lost: f64,
+    // event_time has DEFAULT now()
+}
+
+/// Matches the simulation_ensemble_stats table: one global statistic of
+/// one year, summarized over the replicates that were still running.
+#[derive(Debug, Row, Serialize, Deserialize)]
+pub struct EnsembleStatsRow {
+    pub ensemble_id: String,
+    pub year: u32,
+    pub statistic: String,
+
+    pub replicates: u32,
+    pub mean: f64,
+    pub sd: f64,
+    /// 2.5% quantile.
+    pub lower: f64,
+    /// 97.5% quantile.
+    pub upper: f64,
+    /// Fraction of all replicates extinct by this year.
+    pub extinct_fraction: f64,
     // event_time has DEFAULT now()
 }
 
@@ -35,10 +169,12 @@ pub struct DBCreds {
     pub database: String,
 }
 
-pub async fn insert_global_stats(
+/// Writes a batch of rows into `table` using a single inserter.
+pub async fn insert_rows<T: Row + Serialize>(
     clickhouse_url: &str,
     creds: &DBCreds,
-    rows: &[GlobalStatsRow],
+    table: &str,
+    rows: &[T],
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create a ClickHouse client
     let client = Client::default()
@@ -47,9 +183,8 @@ pub async fn insert_global_stats(
         .with_password(&creds.password)
         .with_database(&creds.database);
 
-    // Build an inserter for the "simulation_yearly_global_stats" table
     let mut inserter = client
-        .inserter("simulation_yearly_global_stats")?
+        .inserter(table)?
         .with_timeouts(Some(Duration::from_secs(5)), Some(Duration::from_secs(20)))
         .with_max_bytes(50_000_000)
         .with_max_rows(750_000)
@@ -67,32 +202,166 @@ pub async fn insert_global_stats(
     Ok(())
 }
 
+pub async fn insert_global_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[GlobalStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_global_stats",
+        rows,
+    )
+    .await
+}
+
 pub async fn insert_meme_stats(
     clickhouse_url: &str,
     creds: &DBCreds,
     rows: &[MemeStatsRow],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Create a ClickHouse client
+    insert_rows(clickhouse_url, creds, "simulation_yearly_meme_stats", rows).await
+}
+
+pub async fn insert_reproduction_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[ReproductionStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_reproduction_stats",
+        rows,
+    )
+    .await
+}
+
+pub async fn insert_resource_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[ResourceStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_resource_stats",
+        rows,
+    )
+    .await
+}
+
+pub async fn insert_group_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[GroupStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(clickhouse_url, creds, "simulation_yearly_group_stats", rows).await
+}
+
+pub async fn insert_conflict_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[ConflictStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_conflict_stats",
+        rows,
+    )
+    .await
+}
+
+pub async fn insert_migration_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[MigrationStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_migration_stats",
+        rows,
+    )
+    .await
+}
+
+pub async fn insert_ensemble_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    rows: &[EnsembleStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    insert_rows(clickhouse_url, creds, "simulation_ensemble_stats", rows).await
+}
+
+/// Reads the rows of one run from `table`, ordered by `order_by`.
+pub async fn select_rows<T: Row + DeserializeOwned>(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    table: &str,
+    simulation_id: &str,
+    order_by: &str,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
     let client = Client::default()
         .with_url(clickhouse_url)
         .with_user(&creds.user)
         .with_password(&creds.password)
         .with_database(&creds.database);
 
-    // Build an inserter for the "simulation_yearly_meme_stats" table
-    let mut inserter = client
-        .inserter("simulation_yearly_meme_stats")?
-        .with_timeouts(Some(Duration::from_secs(5)), Some(Duration::from_secs(20)))
-        .with_max_bytes(50_000_000)
-        .with_max_rows(750_000)
-        .with_period(Some(Duration::from_secs(15)));
+    let rows = client
+        .query(&format!(
+            "SELECT ?fields FROM {} WHERE simulation_id = ? ORDER BY {}",
+            table, order_by
+        ))
+        .bind(simulation_id)
+        .fetch_all::<T>()
+        .await?;
+    Ok(rows)
+}
 
-    for row in rows {
-        inserter.write(row)?;
-    }
+pub async fn load_global_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    simulation_id: &str,
+) -> Result<Vec<GlobalStatsRow>, Box<dyn std::error::Error>> {
+    select_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_global_stats",
+        simulation_id,
+        "year",
+    )
+    .await
+}
 
-    inserter.commit().await?;
-    inserter.end().await?;
+pub async fn load_meme_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    simulation_id: &str,
+) -> Result<Vec<MemeStatsRow>, Box<dyn std::error::Error>> {
+    select_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_meme_stats",
+        simulation_id,
+        "year, meme_kind",
+    )
+    .await
+}
 
-    Ok(())
+pub async fn load_group_stats(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    simulation_id: &str,
+) -> Result<Vec<GroupStatsRow>, Box<dyn std::error::Error>> {
+    select_rows(
+        clickhouse_url,
+        creds,
+        "simulation_yearly_group_stats",
+        simulation_id,
+        "year, group_id",
+    )
+    .await
 }
@@ -0,0 +1,467 @@
+use crate::db::clickhouse_client::*;
+
+use clickhouse::Client;
+use mysql_async::{prelude::*, Pool};
+use rusqlite::Connection;
+
+/// A numbered schema migration from the `sql/` directory.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const CLICKHOUSE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_init",
+        sql: include_str!("../../sql/clickhouse/0000_init.sql"),
+    },
+    Migration {
+        version: 1,
+        name: "0001_reproduction_stats",
+        sql: include_str!("../../sql/clickhouse/0001_reproduction_stats.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "0002_resource_stats",
+        sql: include_str!("../../sql/clickhouse/0002_resource_stats.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "0003_meme_prevalence",
+        sql: include_str!("../../sql/clickhouse/0003_meme_prevalence.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "0004_vertical_transfers",
+        sql: include_str!("../../sql/clickhouse/0004_vertical_transfers.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "0005_group_patch",
+        sql: include_str!("../../sql/clickhouse/0005_group_patch.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "0006_environment",
+        sql: include_str!("../../sql/clickhouse/0006_environment.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "0007_conflict_stats",
+        sql: include_str!("../../sql/clickhouse/0007_conflict_stats.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "0008_meme_depth",
+        sql: include_str!("../../sql/clickhouse/0008_meme_depth.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "0009_copy_errors",
+        sql: include_str!("../../sql/clickhouse/0009_copy_errors.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "0010_sires",
+        sql: include_str!("../../sql/clickhouse/0010_sires.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "0011_migration_stats",
+        sql: include_str!("../../sql/clickhouse/0011_migration_stats.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "0012_population_size",
+        sql: include_str!("../../sql/clickhouse/0012_population_size.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "0013_ensemble_stats",
+        sql: include_str!("../../sql/clickhouse/0013_ensemble_stats.sql"),
+    },
+];
+
+pub const MYSQL_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_init",
+        sql: include_str!("../../sql/mysql/0000_init.sql"),
+    },
+    Migration {
+        version: 1,
+        name: "0001_stop_reason",
+        sql: include_str!("../../sql/mysql/0001_stop_reason.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "0002_ensembles",
+        sql: include_str!("../../sql/mysql/0002_ensembles.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "0003_run_metadata",
+        sql: include_str!("../../sql/mysql/0003_run_metadata.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "0004_failed_replicates",
+        sql: include_str!("../../sql/mysql/0004_failed_replicates.sql"),
+    },
+];
+
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_init",
+        sql: include_str!("../../sql/sqlite/0000_init.sql"),
+    },
+    Migration {
+        version: 1,
+        name: "0001_failed_replicates",
+        sql: include_str!("../../sql/sqlite/0001_failed_replicates.sql"),
+    },
+];
+
+/// Versions up to which the migrations of databases set up by hand are
+/// recorded as applied without running them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Baseline {
+    pub clickhouse: Option<u32>,
+    pub mysql: Option<u32>,
+}
+
+/// Checks that `baseline` names one of `migrations`.
+fn check_baseline(
+    database: &str,
+    baseline: Option<u32>,
+    migrations: &[Migration],
+) -> Result<(), String> {
+    match baseline {
+        Some(version) if version > latest_version(migrations) => Err(format!(
+            "There is no {} migration {} to baseline at",
+            database, version
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Version the code expects, i.e. the number of the latest migration.
+fn latest_version(migrations: &[Migration]) -> u32 {
+    migrations.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Statements of a ClickHouse or MySQL migration file, which are executed
+/// one by one. The file is split at every `;`, so statements must not
+/// contain one elsewhere, e.g. in a string literal or a comment.
+fn statements(sql: &str) -> impl Iterator<Item = &str> {
+    sql.split(';').map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Statements of `migration` still to execute, numbered within the file.
+/// None are left for a migration up to `baseline`, nor those recorded in
+/// `executed` as `(version, statement)` by an earlier, interrupted attempt.
+fn pending_statements<'a>(
+    migration: &'a Migration,
+    executed: &[(u32, u32)],
+    baseline: Option<u32>,
+) -> Vec<(u32, &'a str)> {
+    if baseline.is_some_and(|version| migration.version <= version) {
+        return Vec::new();
+    }
+    statements(migration.sql)
+        .enumerate()
+        .map(|(i, statement)| (i as u32, statement))
+        .filter(|(i, _)| !executed.contains(&(migration.version, *i)))
+        .collect()
+}
+
+fn clickhouse_client(clickhouse_url: &str, creds: &DBCreds) -> Client {
+    Client::default()
+        .with_url(clickhouse_url)
+        .with_user(&creds.user)
+        .with_password(&creds.password)
+        .with_database(&creds.database)
+}
+
+/// Applies the ClickHouse migrations missing from the `schema_migrations`
+/// table and returns the names of the applied ones. Those up to `baseline`
+/// are only recorded. Every executed statement is recorded in
+/// `schema_migration_statements`, so that a migration which failed halfway
+/// resumes after its last successful statement.
+pub async fn migrate_clickhouse(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    baseline: Option<u32>,
+) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+    check_baseline("ClickHouse", baseline, CLICKHOUSE_MIGRATIONS)?;
+    let client = clickhouse_client(clickhouse_url, creds);
+    client
+        .query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations
+            (
+                `version` UInt32,
+                `name` String,
+                `applied_at` DateTime DEFAULT now()
+            )
+            ENGINE = MergeTree
+            ORDER BY version
+        "#,
+        )
+        .execute()
+        .await?;
+    client
+        .query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migration_statements
+            (
+                `version` UInt32,
+                `statement` UInt32,
+                `applied_at` DateTime DEFAULT now()
+            )
+            ENGINE = MergeTree
+            ORDER BY (version, statement)
+        "#,
+        )
+        .execute()
+        .await?;
+    let applied: Vec<u32> = client
+        .query("SELECT version FROM schema_migrations")
+        .fetch_all()
+        .await?;
+    let executed: Vec<(u32, u32)> = client
+        .query("SELECT version, statement FROM schema_migration_statements")
+        .fetch_all()
+        .await?;
+
+    let mut names = Vec::new();
+    for migration in CLICKHOUSE_MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+    {
+        for (i, statement) in pending_statements(migration, &executed, baseline) {
+            client.query(statement).execute().await?;
+            client
+                .query("INSERT INTO schema_migration_statements (version, statement) VALUES (?, ?)")
+                .bind(migration.version)
+                .bind(i)
+                .execute()
+                .await?;
+        }
+        client
+            .query("INSERT INTO schema_migrations (version, name) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute()
+            .await?;
+        if baseline.is_none_or(|version| migration.version > version) {
+            names.push(migration.name);
+        }
+    }
+    Ok(names)
+}
+
+/// Applies the MySQL migrations missing from the `schema_migrations` table
+/// and returns the names of the applied ones, like `migrate_clickhouse`.
+pub async fn migrate_mysql(
+    pool: &Pool,
+    baseline: Option<u32>,
+) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+    check_baseline("MySQL", baseline, MYSQL_MIGRATIONS)?;
+    let mut conn = pool.get_conn().await?;
+    conn.query_drop(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+          version INT UNSIGNED PRIMARY KEY,
+          name VARCHAR(255) NOT NULL,
+          applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )
+    "#,
+    )
+    .await?;
+    conn.query_drop(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migration_statements (
+          version INT UNSIGNED NOT NULL,
+          statement INT UNSIGNED NOT NULL,
+          applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+          PRIMARY KEY (version, statement)
+        )
+    "#,
+    )
+    .await?;
+    let applied: Vec<u32> = conn.query("SELECT version FROM schema_migrations").await?;
+    let executed: Vec<(u32, u32)> = conn
+        .query("SELECT version, statement FROM schema_migration_statements")
+        .await?;
+
+    let mut names = Vec::new();
+    for migration in MYSQL_MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+    {
+        for (i, statement) in pending_statements(migration, &executed, baseline) {
+            conn.query_drop(statement).await?;
+            conn.exec_drop(
+                "INSERT INTO schema_migration_statements (version, statement) VALUES (:version, :statement)",
+                params! {
+                    "version" => migration.version,
+                    "statement" => i,
+                },
+            )
+            .await?;
+        }
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+            params! {
+                "version" => migration.version,
+                "name" => migration.name,
+            },
+        )
+        .await?;
+        if baseline.is_none_or(|version| migration.version > version) {
+            names.push(migration.name);
+        }
+    }
+    Ok(names)
+}
+
+/// Checks that both databases are at the schema version this build writes,
+/// so that no run starts against tables lacking some of its columns.
+pub async fn check_schema_versions(
+    clickhouse_url: &str,
+    creds: &DBCreds,
+    pool: &Pool,
+) -> Result<(), String> {
+    let hint = "run `tribesim migrate` first";
+    let clickhouse_version: u32 = clickhouse_client(clickhouse_url, creds)
+        .query("SELECT max(version) FROM schema_migrations")
+        .fetch_one()
+        .await
+        .map_err(|e| {
+            format!(
+                "Cannot read the ClickHouse schema version ({}); {}",
+                e, hint
+            )
+        })?;
+    let expected = latest_version(CLICKHOUSE_MIGRATIONS);
+    if clickhouse_version < expected {
+        return Err(format!(
+            "ClickHouse schema is at version {}, {} is required; {}",
+            clickhouse_version, expected, hint
+        ));
+    }
+
+    let mut conn = pool
+        .get_conn()
+        .await
+        .map_err(|e| format!("Cannot connect to MySQL: {}", e))?;
+    let mysql_version: Option<u32> = conn
+        .query_first("SELECT MAX(version) FROM schema_migrations")
+        .await
+        .map_err(|e| format!("Cannot read the MySQL schema version ({}); {}", e, hint))?;
+    let mysql_version = mysql_version.unwrap_or(0);
+    let expected = latest_version(MYSQL_MIGRATIONS);
+    if mysql_version < expected {
+        return Err(format!(
+            "MySQL schema is at version {}, {} is required; {}",
+            mysql_version, expected, hint
+        ));
+    }
+    Ok(())
+}
+
+/// Applies the SQLite migrations missing from the `schema_migrations` table,
+/// each in its own transaction, and returns the names of the applied ones.
+pub fn migrate_sqlite(conn: &mut Connection) -> rusqlite::Result<Vec<&'static str>> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+          version INTEGER PRIMARY KEY,
+          name TEXT NOT NULL,
+          applied_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )
+    "#,
+    )?;
+    let applied = conn
+        .prepare("SELECT version FROM schema_migrations")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<u32>>>()?;
+
+    let mut names = Vec::new();
+    for migration in SQLITE_MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+    {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+            (migration.version, migration.name),
+        )?;
+        tx.commit()?;
+        names.push(migration.name);
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_statements_at_semicolons() {
+        let sql = "CREATE TABLE a (x Int32);\n\n  ALTER TABLE a ADD COLUMN y Int32 ;\n";
+        assert_eq!(
+            statements(sql).collect::<Vec<_>>(),
+            [
+                "CREATE TABLE a (x Int32)",
+                "ALTER TABLE a ADD COLUMN y Int32"
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupted_and_baselined_migrations_skip_statements() {
+        let migration = Migration {
+            version: 3,
+            name: "0003_run_metadata",
+            sql: "ALTER TABLE a ADD COLUMN x Int32;\nALTER TABLE a ADD COLUMN y Int32;",
+        };
+        assert_eq!(
+            pending_statements(&migration, &[], None),
+            [
+                (0, "ALTER TABLE a ADD COLUMN x Int32"),
+                (1, "ALTER TABLE a ADD COLUMN y Int32")
+            ]
+        );
+        // The first statement succeeded before the last attempt failed
+        assert_eq!(
+            pending_statements(&migration, &[(2, 1), (3, 0)], None),
+            [(1, "ALTER TABLE a ADD COLUMN y Int32")]
+        );
+        assert!(pending_statements(&migration, &[(3, 0), (3, 1)], None).is_empty());
+        assert!(pending_statements(&migration, &[], Some(3)).is_empty());
+        assert_eq!(pending_statements(&migration, &[], Some(2)).len(), 2);
+
+        assert!(check_baseline("MySQL", Some(0), MYSQL_MIGRATIONS).is_ok());
+        assert!(check_baseline("MySQL", None, MYSQL_MIGRATIONS).is_ok());
+        let too_new = latest_version(MYSQL_MIGRATIONS) + 1;
+        assert!(check_baseline("MySQL", Some(too_new), MYSQL_MIGRATIONS).is_err());
+    }
+
+    #[test]
+    fn migrations_are_numbered_in_order() {
+        for migrations in [CLICKHOUSE_MIGRATIONS, MYSQL_MIGRATIONS, SQLITE_MIGRATIONS] {
+            for (i, migration) in migrations.iter().enumerate() {
+                assert_eq!(migration.version as usize, i);
+                assert!(migration.name.starts_with(&format!("{:04}_", i)));
+                assert!(statements(migration.sql).next().is_some());
+            }
+        }
+    }
+}
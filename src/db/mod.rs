@@ -1,2 +1,6 @@
+pub mod backend;
 pub mod clickhouse_client;
+pub mod migrations;
 pub mod mysql_client;
+pub mod parquet_writer;
+pub mod sqlite_client;
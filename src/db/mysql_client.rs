@@ -1,4 +1,5 @@
 use crate::config::config::*;
+use crate::db::backend::RunState;
 use mysql_async::{prelude::*, Pool};
 use serde_json;
 
@@ -65,3 +66,197 @@ pub async fn load_simulation_config(
         Err(format!("No config found for run_uuid = {}", run_uuid).into())
     }
 }
+
+/// Records the start of a run and the environment it runs in.
+pub async fn store_run_start(
+    pool: &Pool,
+    run_uuid: &str,
+    seed: u64,
+    code_version: &str,
+    host: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        UPDATE simulation_runs
+        SET state = :state, started_at = NOW(), seed = :seed,
+            code_version = :code_version, host = :host
+        WHERE run_uuid = :run_uuid
+    "#,
+        params! {
+            "run_uuid" => run_uuid,
+            "state" => RunState::Running.to_string(),
+            "seed" => seed,
+            "code_version" => code_version,
+            "host" => host,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Records the last year a running simulation has reached.
+pub async fn store_run_progress(
+    pool: &Pool,
+    run_uuid: &str,
+    last_year: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        "UPDATE simulation_runs SET last_year = :last_year WHERE run_uuid = :run_uuid",
+        params! {
+            "run_uuid" => run_uuid,
+            "last_year" => last_year,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Records how a run ended, with the global statistics of its last year
+/// serialized to JSON.
+pub async fn store_run_result(
+    pool: &Pool,
+    run_uuid: &str,
+    state: RunState,
+    stop_reason: &str,
+    last_year: usize,
+    summary: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        UPDATE simulation_runs
+        SET state = :state, stop_reason = :stop_reason, last_year = :last_year,
+            summary = :summary, finished_at = NOW()
+        WHERE run_uuid = :run_uuid
+    "#,
+        params! {
+            "run_uuid" => run_uuid,
+            "state" => state.to_string(),
+            "stop_reason" => stop_reason,
+            "last_year" => last_year,
+            "summary" => summary,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Records the state of a run that ended without results.
+pub async fn store_run_state(
+    pool: &Pool,
+    run_uuid: &str,
+    state: RunState,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        UPDATE simulation_runs
+        SET state = :state, finished_at = NOW()
+        WHERE run_uuid = :run_uuid
+    "#,
+        params! {
+            "run_uuid" => run_uuid,
+            "state" => state.to_string(),
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Marks a run as replicate `replicate` of an ensemble.
+pub async fn store_replicate_tag(
+    pool: &Pool,
+    run_uuid: &str,
+    ensemble_uuid: &str,
+    replicate: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        UPDATE simulation_runs
+        SET ensemble_uuid = :ensemble_uuid, replicate = :replicate
+        WHERE run_uuid = :run_uuid
+    "#,
+        params! {
+            "run_uuid" => run_uuid,
+            "ensemble_uuid" => ensemble_uuid,
+            "replicate" => replicate,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Insert an ensemble into the simulation_ensembles table.
+pub async fn store_ensemble(
+    pool: &Pool,
+    ensemble_uuid: &str,
+    replicates: usize,
+    base_seed: u64,
+    config: &SimConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config_json = serde_json::to_string(config)?;
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        INSERT INTO simulation_ensembles (ensemble_uuid, replicates, base_seed, config)
+        VALUES (:ensemble_uuid, :replicates, :base_seed, :config_json)
+    "#,
+        params! {
+            "ensemble_uuid" => ensemble_uuid,
+            "replicates" => replicates,
+            "base_seed" => base_seed,
+            "config_json" => config_json,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Records the fraction of the finished replicates of an ensemble that went
+/// extinct, and the number of replicates that failed.
+pub async fn store_ensemble_result(
+    pool: &Pool,
+    ensemble_uuid: &str,
+    extinct_fraction: f64,
+    failed_replicates: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    conn.exec_drop(
+        r#"
+        UPDATE simulation_ensembles
+        SET extinct_fraction = :extinct_fraction, failed_replicates = :failed_replicates
+        WHERE ensemble_uuid = :ensemble_uuid
+    "#,
+        params! {
+            "ensemble_uuid" => ensemble_uuid,
+            "extinct_fraction" => extinct_fraction,
+            "failed_replicates" => failed_replicates,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// The ensemble a run belongs to, if any.
+pub async fn load_ensemble_uuid(
+    pool: &Pool,
+    run_uuid: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut conn = pool.get_conn().await?;
+    let row_opt: Option<(Option<String>,)> = conn
+        .exec_first(
+            "SELECT ensemble_uuid FROM simulation_runs WHERE run_uuid = :run_uuid",
+            params! {
+                "run_uuid" => run_uuid,
+            },
+        )
+        .await?;
+
+    match row_opt {
+        Some((ensemble_uuid,)) => Ok(ensemble_uuid),
+        None => Err(format!("No run found for run_uuid = {}", run_uuid).into()),
+    }
+}
@@ -0,0 +1,242 @@
+use crate::db::backend::*;
+use crate::db::clickhouse_client::*;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const GLOBAL_TABLE: &str = "simulation_yearly_global_stats";
+pub const MEME_TABLE: &str = "simulation_yearly_meme_stats";
+pub const GROUP_TABLE: &str = "simulation_yearly_group_stats";
+
+/// Hive-style partition of the files of one run. Every run sits below its
+/// ensemble, `none` for runs outside of any, so that all runs written to
+/// the same directory have the same layout.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub ensemble_uuid: Option<String>,
+    pub run_uuid: String,
+}
+
+impl Partition {
+    /// `<root>/<table>/ensemble_uuid=<uuid>/run_uuid=<uuid>`
+    pub fn dir(&self, root: &str, table: &str) -> PathBuf {
+        PathBuf::from(root)
+            .join(table)
+            .join(format!(
+                "ensemble_uuid={}",
+                self.ensemble_uuid.as_deref().unwrap_or("none")
+            ))
+            .join(format!("run_uuid={}", self.run_uuid))
+    }
+}
+
+fn strings<'a>(values: impl Iterator<Item = &'a str>) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(values))
+}
+
+fn u32s(values: impl Iterator<Item = u32>) -> ArrayRef {
+    Arc::new(UInt32Array::from_iter_values(values))
+}
+
+fn u64s(values: impl Iterator<Item = u64>) -> ArrayRef {
+    Arc::new(UInt64Array::from_iter_values(values))
+}
+
+fn f64s(values: impl Iterator<Item = f64>) -> ArrayRef {
+    Arc::new(Float64Array::from_iter_values(values))
+}
+
+fn field(name: &str, data_type: DataType) -> Field {
+    Field::new(name, data_type, false)
+}
+
+/// Columns with the types of the simulation_yearly_global_stats table.
+pub fn global_batch(rows: &[GlobalStatsRow]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        field("simulation_id", DataType::Utf8),
+        field("year", DataType::UInt32),
+        field("total_memes_known", DataType::UInt64),
+        field("avg_memes_known", DataType::Float64),
+        field("avg_trick_efficiency", DataType::Float64),
+        field("avg_brain_volume", DataType::Float64),
+        field("avg_meme_size", DataType::Float64),
+        field("resources", DataType::Float64),
+        field("shock", DataType::Boolean),
+        field("max_meme_depth", DataType::UInt32),
+        field("avg_meme_depth", DataType::Float64),
+        field("headcount", DataType::UInt64),
+        field("group_count", DataType::UInt32),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            strings(rows.iter().map(|r| r.simulation_id.as_str())),
+            u32s(rows.iter().map(|r| r.year)),
+            u64s(rows.iter().map(|r| r.total_memes_known)),
+            f64s(rows.iter().map(|r| r.avg_memes_known)),
+            f64s(rows.iter().map(|r| r.avg_trick_efficiency)),
+            f64s(rows.iter().map(|r| r.avg_brain_volume)),
+            f64s(rows.iter().map(|r| r.avg_meme_size)),
+            f64s(rows.iter().map(|r| r.resources)),
+            Arc::new(BooleanArray::from_iter(rows.iter().map(|r| Some(r.shock)))),
+            u32s(rows.iter().map(|r| r.max_meme_depth)),
+            f64s(rows.iter().map(|r| r.avg_meme_depth)),
+            u64s(rows.iter().map(|r| r.headcount)),
+            u32s(rows.iter().map(|r| r.group_count)),
+        ],
+    )
+}
+
+/// Columns with the types of the simulation_yearly_meme_stats table.
+pub fn meme_batch(rows: &[MemeStatsRow]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        field("simulation_id", DataType::Utf8),
+        field("year", DataType::UInt32),
+        field("meme_kind", DataType::Utf8),
+        field("avg_meme_efficiency", DataType::Float64),
+        field("avg_meme_size", DataType::Float64),
+        field("copies", DataType::UInt64),
+        field("distinct_memes", DataType::UInt64),
+        field("carrier_fraction", DataType::Float64),
+        field("inventions", DataType::UInt64),
+        field("transfers", DataType::UInt64),
+        field("vertical_transfers", DataType::UInt64),
+        field("losses", DataType::UInt64),
+        field("copy_errors", DataType::UInt64),
+        field("max_depth", DataType::UInt32),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            strings(rows.iter().map(|r| r.simulation_id.as_str())),
+            u32s(rows.iter().map(|r| r.year)),
+            strings(rows.iter().map(|r| r.meme_kind.as_str())),
+            f64s(rows.iter().map(|r| r.avg_meme_efficiency)),
+            f64s(rows.iter().map(|r| r.avg_meme_size)),
+            u64s(rows.iter().map(|r| r.copies)),
+            u64s(rows.iter().map(|r| r.distinct_memes)),
+            f64s(rows.iter().map(|r| r.carrier_fraction)),
+            u64s(rows.iter().map(|r| r.inventions)),
+            u64s(rows.iter().map(|r| r.transfers)),
+            u64s(rows.iter().map(|r| r.vertical_transfers)),
+            u64s(rows.iter().map(|r| r.losses)),
+            u64s(rows.iter().map(|r| r.copy_errors)),
+            u32s(rows.iter().map(|r| r.max_depth)),
+        ],
+    )
+}
+
+/// Columns with the types of the simulation_yearly_group_stats table.
+pub fn group_batch(rows: &[GroupStatsRow]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        field("simulation_id", DataType::Utf8),
+        field("year", DataType::UInt32),
+        field("group_id", DataType::UInt64),
+        Field::new("patch", DataType::UInt32, true),
+        field("headcount", DataType::UInt64),
+        field("resource_share", DataType::Float64),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            strings(rows.iter().map(|r| r.simulation_id.as_str())),
+            u32s(rows.iter().map(|r| r.year)),
+            u64s(rows.iter().map(|r| r.group_id)),
+            Arc::new(UInt32Array::from_iter(rows.iter().map(|r| r.patch))),
+            u64s(rows.iter().map(|r| r.headcount)),
+            f64s(rows.iter().map(|r| r.resource_share)),
+        ],
+    )
+}
+
+/// Writes `batch` as one snappy-compressed file named after its first year,
+/// so that successive batches of a run end up in separate files.
+fn write_batch(
+    root: &str,
+    table: &str,
+    partition: &Partition,
+    first_year: u32,
+    batch: RecordBatch,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = partition.dir(root, table);
+    fs::create_dir_all(&dir)?;
+    let file = File::create(dir.join(format!("part-{:07}.parquet", first_year)))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Writes the global, meme and group rows of one run below `root`. Tables
+/// without rows are skipped.
+pub fn write_stats(
+    root: &str,
+    partition: &Partition,
+    global: &[GlobalStatsRow],
+    meme: &[MemeStatsRow],
+    group: &[GroupStatsRow],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(first) = global.first() {
+        write_batch(
+            root,
+            GLOBAL_TABLE,
+            partition,
+            first.year,
+            global_batch(global)?,
+        )?;
+    }
+    if let Some(first) = meme.first() {
+        write_batch(root, MEME_TABLE, partition, first.year, meme_batch(meme)?)?;
+    }
+    if let Some(first) = group.first() {
+        write_batch(
+            root,
+            GROUP_TABLE,
+            partition,
+            first.year,
+            group_batch(group)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes the files previously written for a run, so that an export does
+/// not leave stale parts next to the new ones.
+pub fn clear_partition(root: &str, partition: &Partition) -> std::io::Result<()> {
+    for table in [GLOBAL_TABLE, MEME_TABLE, GROUP_TABLE] {
+        let dir = partition.dir(root, table);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Exports the statistics of a finished run, read back from `backend`, to
+/// Parquet files below `root`.
+pub async fn export_run(
+    backend: &Backend,
+    run_uuid: &str,
+    root: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let partition = Partition {
+        ensemble_uuid: backend.load_ensemble_uuid(run_uuid).await?,
+        run_uuid: run_uuid.to_string(),
+    };
+    let global = backend.load_global_stats(run_uuid).await?;
+    let meme = backend.load_meme_stats(run_uuid).await?;
+    let group = backend.load_group_stats(run_uuid).await?;
+    clear_partition(root, &partition)?;
+    write_stats(root, &partition, &global, &meme, &group)
+}
@@ -0,0 +1,353 @@
+use crate::config::config::*;
+use crate::db::backend::RunState;
+use crate::db::clickhouse_client::*;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+type SqliteResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Connection to a SQLite database, shared by all runs of the process.
+/// Queries run one at a time on a blocking thread.
+#[derive(Debug, Clone)]
+pub struct SqliteDb {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDb {
+    /// Opens the database at `path`, creating the file if needed. Pending
+    /// migrations are not applied.
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // Other processes may write to the same file
+        conn.busy_timeout(Duration::from_secs(30))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `query` on the connection without blocking the async runtime.
+    /// Fails if an earlier query panicked while holding the connection.
+    pub async fn run<T, F>(&self, query: F) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> SqliteResult<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| "SQLite connection poisoned by a panicking query")?;
+            query(&mut conn)
+        })
+        .await?
+        .map_err(|e| e as Box<dyn std::error::Error>)
+    }
+}
+
+/// Insert a run into the simulation_runs table, storing the config as JSON.
+pub fn store_simulation_config(
+    conn: &Connection,
+    run_uuid: &str,
+    config: &SimConfig,
+) -> SqliteResult<()> {
+    let config_json = serde_json::to_string(config)?;
+    conn.execute(
+        "INSERT INTO simulation_runs (run_uuid, config) VALUES (?1, ?2)",
+        params![run_uuid, config_json],
+    )?;
+    Ok(())
+}
+
+pub fn load_simulation_config(conn: &Connection, run_uuid: &str) -> SqliteResult<SimConfig> {
+    let config_json: Option<String> = conn
+        .query_row(
+            "SELECT config FROM simulation_runs WHERE run_uuid = ?1",
+            params![run_uuid],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(config_json) = config_json {
+        let sim_config: SimConfig = serde_json::from_str(&config_json)?;
+        Ok(sim_config)
+    } else {
+        Err(format!("No config found for run_uuid = {}", run_uuid).into())
+    }
+}
+
+/// Records the start of a run and the environment it runs in. The seed is
+/// stored as a signed integer with the same bits, SQLite has no unsigned type.
+pub fn store_run_start(
+    conn: &Connection,
+    run_uuid: &str,
+    seed: u64,
+    code_version: &str,
+    host: &str,
+) -> SqliteResult<()> {
+    conn.execute(
+        r#"
+        UPDATE simulation_runs
+        SET state = ?5, started_at = CURRENT_TIMESTAMP, seed = ?2,
+            code_version = ?3, host = ?4
+        WHERE run_uuid = ?1
+    "#,
+        params![
+            run_uuid,
+            seed as i64,
+            code_version,
+            host,
+            RunState::Running.to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Records the last year a running simulation has reached.
+pub fn store_run_progress(conn: &Connection, run_uuid: &str, last_year: usize) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE simulation_runs SET last_year = ?2 WHERE run_uuid = ?1",
+        params![run_uuid, last_year],
+    )?;
+    Ok(())
+}
+
+/// Records how a run ended, with the global statistics of its last year
+/// serialized to JSON.
+pub fn store_run_result(
+    conn: &Connection,
+    run_uuid: &str,
+    state: RunState,
+    stop_reason: &str,
+    last_year: usize,
+    summary: &str,
+) -> SqliteResult<()> {
+    conn.execute(
+        r#"
+        UPDATE simulation_runs
+        SET state = ?2, stop_reason = ?3, last_year = ?4,
+            summary = ?5, finished_at = CURRENT_TIMESTAMP
+        WHERE run_uuid = ?1
+    "#,
+        params![run_uuid, state.to_string(), stop_reason, last_year, summary],
+    )?;
+    Ok(())
+}
+
+/// Records the state of a run that ended without results.
+pub fn store_run_state(conn: &Connection, run_uuid: &str, state: RunState) -> SqliteResult<()> {
+    conn.execute(
+        r#"
+        UPDATE simulation_runs
+        SET state = ?2, finished_at = CURRENT_TIMESTAMP
+        WHERE run_uuid = ?1
+    "#,
+        params![run_uuid, state.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Marks a run as replicate `replicate` of an ensemble.
+pub fn store_replicate_tag(
+    conn: &Connection,
+    run_uuid: &str,
+    ensemble_uuid: &str,
+    replicate: usize,
+) -> SqliteResult<()> {
+    conn.execute(
+        "UPDATE simulation_runs SET ensemble_uuid = ?2, replicate = ?3 WHERE run_uuid = ?1",
+        params![run_uuid, ensemble_uuid, replicate],
+    )?;
+    Ok(())
+}
+
+/// Insert an ensemble into the simulation_ensembles table.
+pub fn store_ensemble(
+    conn: &Connection,
+    ensemble_uuid: &str,
+    replicates: usize,
+    base_seed: u64,
+    config: &SimConfig,
+) -> SqliteResult<()> {
+    let config_json = serde_json::to_string(config)?;
+    conn.execute(
+        r#"
+        INSERT INTO simulation_ensembles (ensemble_uuid, replicates, base_seed, config)
+        VALUES (?1, ?2, ?3, ?4)
+    "#,
+        params![ensemble_uuid, replicates, base_seed as i64, config_json],
+    )?;
+    Ok(())
+}
+
+/// Records the fraction of the finished replicates of an ensemble that went
+/// extinct, and the number of replicates that failed.
+pub fn store_ensemble_result(
+    conn: &Connection,
+    ensemble_uuid: &str,
+    extinct_fraction: f64,
+    failed_replicates: usize,
+) -> SqliteResult<()> {
+    conn.execute(
+        r#"
+        UPDATE simulation_ensembles
+        SET extinct_fraction = ?2, failed_replicates = ?3
+        WHERE ensemble_uuid = ?1
+    "#,
+        params![ensemble_uuid, extinct_fraction, failed_replicates],
+    )?;
+    Ok(())
+}
+
+/// Writes the yearly global and meme statistics in a single transaction.
+pub fn insert_stats(
+    conn: &mut Connection,
+    global: &[GlobalStatsRow],
+    meme: &[MemeStatsRow],
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut insert_global = tx.prepare(
+            r#"
+            INSERT INTO simulation_yearly_global_stats (
+              simulation_id, year, total_memes_known, avg_memes_known,
+              avg_trick_efficiency, avg_brain_volume, avg_meme_size, resources,
+              shock, max_meme_depth, avg_meme_depth, headcount, group_count
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        "#,
+        )?;
+        for row in global {
+            insert_global.execute(params![
+                row.simulation_id,
+                row.year,
+                row.total_memes_known,
+                row.avg_memes_known,
+                row.avg_trick_efficiency,
+                row.avg_brain_volume,
+                row.avg_meme_size,
+                row.resources,
+                row.shock,
+                row.max_meme_depth,
+                row.avg_meme_depth,
+                row.headcount,
+                row.group_count,
+            ])?;
+        }
+
+        let mut insert_meme = tx.prepare(
+            r#"
+            INSERT INTO simulation_yearly_meme_stats (
+              simulation_id, year, meme_kind, avg_meme_efficiency, avg_meme_size,
+              copies, distinct_memes, carrier_fraction, inventions, transfers,
+              vertical_transfers, losses, copy_errors, max_depth
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        "#,
+        )?;
+        for row in meme {
+            insert_meme.execute(params![
+                row.simulation_id,
+                row.year,
+                row.meme_kind,
+                row.avg_meme_efficiency,
+                row.avg_meme_size,
+                row.copies,
+                row.distinct_memes,
+                row.carrier_fraction,
+                row.inventions,
+                row.transfers,
+                row.vertical_transfers,
+                row.losses,
+                row.copy_errors,
+                row.max_depth,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// The ensemble a run belongs to, if any.
+pub fn load_ensemble_uuid(conn: &Connection, run_uuid: &str) -> SqliteResult<Option<String>> {
+    let ensemble_uuid: Option<Option<String>> = conn
+        .query_row(
+            "SELECT ensemble_uuid FROM simulation_runs WHERE run_uuid = ?1",
+            params![run_uuid],
+            |row| row.get(0),
+        )
+        .optional()?;
+    ensemble_uuid.ok_or_else(|| format!("No run found for run_uuid = {}", run_uuid).into())
+}
+
+pub fn load_global_stats(
+    conn: &Connection,
+    simulation_id: &str,
+) -> SqliteResult<Vec<GlobalStatsRow>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT simulation_id, year, total_memes_known, avg_memes_known,
+               avg_trick_efficiency, avg_brain_volume, avg_meme_size, resources,
+               shock, max_meme_depth, avg_meme_depth, headcount, group_count
+        FROM simulation_yearly_global_stats
+        WHERE simulation_id = ?1
+        ORDER BY year
+    "#,
+    )?;
+    let rows = stmt
+        .query_map(params![simulation_id], |row| {
+            Ok(GlobalStatsRow {
+                simulation_id: row.get(0)?,
+                year: row.get(1)?,
+                total_memes_known: row.get(2)?,
+                avg_memes_known: row.get(3)?,
+                avg_trick_efficiency: row.get(4)?,
+                avg_brain_volume: row.get(5)?,
+                avg_meme_size: row.get(6)?,
+                resources: row.get(7)?,
+                shock: row.get(8)?,
+                max_meme_depth: row.get(9)?,
+                avg_meme_depth: row.get(10)?,
+                headcount: row.get(11)?,
+                group_count: row.get(12)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+pub fn load_meme_stats(conn: &Connection, simulation_id: &str) -> SqliteResult<Vec<MemeStatsRow>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT simulation_id, year, meme_kind, avg_meme_efficiency, avg_meme_size,
+               copies, distinct_memes, carrier_fraction, inventions, transfers,
+               vertical_transfers, losses, copy_errors, max_depth
+        FROM simulation_yearly_meme_stats
+        WHERE simulation_id = ?1
+        ORDER BY year, meme_kind
+    "#,
+    )?;
+    let rows = stmt
+        .query_map(params![simulation_id], |row| {
+            Ok(MemeStatsRow {
+                simulation_id: row.get(0)?,
+                year: row.get(1)?,
+                meme_kind: row.get(2)?,
+                avg_meme_efficiency: row.get(3)?,
+                avg_meme_size: row.get(4)?,
+                copies: row.get(5)?,
+                distinct_memes: row.get(6)?,
+                carrier_fraction: row.get(7)?,
+                inventions: row.get(8)?,
+                transfers: row.get(9)?,
+                vertical_transfers: row.get(10)?,
+                losses: row.get(11)?,
+                copy_errors: row.get(12)?,
+                max_depth: row.get(13)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
@@ -1,24 +1,34 @@
 pub mod simulation;
 pub use simulation::agent::*;
+pub use simulation::environment::*;
 pub use simulation::group::*;
+pub use simulation::landscape::*;
 pub use simulation::memetics::*;
 
 pub mod model;
+pub use model::conflict::*;
 pub use model::distribution::*;
 pub use model::population::*;
 pub use model::reproduction::*;
 
 pub mod db;
+pub use db::backend::*;
 pub use db::clickhouse_client::*;
+pub use db::migrations::*;
 pub use db::mysql_client::*;
+pub use db::parquet_writer::*;
 
 pub mod config;
 pub use config::config::*;
+pub use config::expression::*;
 pub use config::file::*;
 
 pub mod runtime;
+pub use runtime::cancel::*;
+pub use runtime::ensemble::*;
 pub use runtime::run_sim::*;
 pub use runtime::statistics::*;
+pub use runtime::stop::*;
 
 pub mod api;
 pub use api::api_server::*;
@@ -1,33 +1,89 @@
 use tribesim::api::api_server::*;
 use tribesim::cli::args::*;
 use tribesim::config::file::*;
+use tribesim::db::backend::*;
+use tribesim::db::migrations::*;
+use tribesim::db::parquet_writer::*;
+use tribesim::runtime::cancel::*;
+use tribesim::runtime::ensemble::*;
 use tribesim::runtime::run_sim::*;
 use tribesim::utils::*;
 
 use clap::Parser;
 use std::env;
-use tokio;
+use std::process::ExitCode;
+use tokio::task;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = Args::parse();
+    let backend = Backend::from_env();
+    match &args.command {
+        Some(Command::Migrate {
+            clickhouse_baseline,
+            mysql_baseline,
+        }) => {
+            let baseline = Baseline {
+                clickhouse: *clickhouse_baseline,
+                mysql: *mysql_baseline,
+            };
+            return match backend.migrate(baseline).await {
+                Ok(applied) => {
+                    println!("Applied {} migrations {:?}", applied.len(), applied);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    println!("Failed to migrate: {}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Export { run_uuid, dir }) => {
+            return match export_run(&backend, run_uuid, dir).await {
+                Ok(_) => {
+                    println!("Exported run {} to {}", run_uuid, dir);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    println!("Failed to export run {}: {}", run_uuid, e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        None => {}
+    }
+
+    if let Err(e) = backend.check_schema().await {
+        println!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
     if !args.launch_server {
         let config_path = env::var("SIM_CONFIG").expect("SIM_CONFIG must be set");
-        match load_config_from_json(config_path.as_str()) {
-            Ok(c) => {
-                initiate_run(generate_uuid(), c).await;
+        task::spawn(cancel_on_interrupt());
+        let succeeded = match load_config_from_json(config_path.as_str()) {
+            Ok(c) if args.replicates > 1 => {
+                initiate_ensemble(generate_uuid(), c, args.replicates).await == Some(0)
             }
+            Ok(c) => initiate_run(generate_uuid(), c).await.is_some(),
             Err(e) => {
                 println!("Error loading config from file: {:?}", e);
+                return ExitCode::FAILURE;
             }
+        };
+        if !succeeded {
+            return ExitCode::FAILURE;
         }
     } else {
         let rocket = start_api_server();
-        match rocket.launch().await {
-            Ok(_) => {}
-            Err(e) => {
-                println!("Failed to start web server: {}", e);
-            }
+        let launched = rocket.launch().await;
+        // The server stopped, e.g. on Ctrl-C: let its runs store their state
+        request_cancel_all();
+        wait_for_runs().await;
+        if let Err(e) = launched {
+            println!("Failed to start web server: {}", e);
+            return ExitCode::FAILURE;
         }
     }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,211 @@
+use crate::config::config::*;
+use crate::model::distribution::*;
+use crate::simulation::group::*;
+use crate::simulation::landscape::*;
+use crate::utils::*;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Summary of the conflicts fought in one simulated year.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConflictOutcome {
+    pub conflicts: u64,
+    pub attacker_wins: u64,
+    pub casualties: u64,
+    pub resources_looted: f64,
+}
+
+fn group_strength(group: &Group, cfg: &ConflictCfg) -> f64 {
+    let warfare: f64 = group.members.iter().map(|a| a.warfare_efficiency).sum();
+    let hunting: f64 = group.members.iter().map(|a| a.tot_hunting_efficiency).sum();
+    cfg.size_weight * group.members.len() as f64
+        + cfg.warfare_weight * warfare
+        + cfg.hunting_weight * hunting
+}
+
+/// Picks the group raided by `groups[attacker]`: any other group, or only the
+/// groups within the competition radius when the landscape is enabled.
+fn choose_target<R: Rng + ?Sized>(
+    groups: &[Group],
+    attacker: usize,
+    landscape: Option<&Landscape>,
+    rng: &mut R,
+) -> Option<usize> {
+    let targets: Vec<usize> = (0..groups.len())
+        .filter(|&i| i != attacker && !groups[i].members.is_empty())
+        .filter(
+            |&i| match (landscape, groups[attacker].patch, groups[i].patch) {
+                (Some(landscape), Some(a), Some(b)) => {
+                    landscape.distance(a, b) <= landscape.config.competition_radius
+                }
+                _ => true,
+            },
+        )
+        .collect();
+    targets.choose(rng).copied()
+}
+
+/// Winners take `loot_fraction` of every loser's resources and share them
+/// equally; then each loser dies with probability `casualty_rate`.
+fn settle<R: Rng + ?Sized>(
+    winner: &mut Group,
+    loser: &mut Group,
+    cfg: &ConflictCfg,
+    rng: &mut R,
+    outcome: &mut ConflictOutcome,
+    flow: &mut ResourceFlow,
+) {
+    let mut loot: f64 = 0.0;
+    for agent in loser.members.iter_mut() {
+        let taken = cfg.loot_fraction.clamp(0.0, 1.0) * agent.resources;
+        agent.resources -= taken;
+        loot += taken;
+    }
+    if !winner.members.is_empty() {
+        let each = loot / winner.members.len() as f64;
+        winner.members.iter_mut().for_each(|a| a.resources += each);
+        outcome.resources_looted += loot;
+    }
+
+    let before_count = loser.members.len();
+    loser.members.retain(|agent| {
+        if rng.gen::<f64>() < cfg.casualty_rate {
+            flow.lost_with_dead += agent.resources;
+            return false;
+        }
+        true
+    });
+    outcome.casualties += (before_count - loser.members.len()) as u64;
+}
+
+pub fn perform_conflicts<R: Rng + ?Sized>(
+    groups: &mut [Group],
+    cfg: &ConflictCfg,
+    landscape: Option<&Landscape>,
+    rng: &mut R,
+    flow: &mut ResourceFlow,
+) -> ConflictOutcome {
+    let mut outcome = ConflictOutcome::default();
+    if groups.len() < 2 {
+        return outcome;
+    }
+
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.shuffle(rng);
+
+    for attacker_idx in order {
+        if groups[attacker_idx].members.is_empty() || rng.gen::<f64>() >= cfg.raid_probability {
+            continue;
+        }
+        let defender_idx = match choose_target(groups, attacker_idx, landscape, rng) {
+            Some(idx) => idx,
+            None => continue,
+        };
+
+        let (attacker, defender) = get_two_mut(groups, attacker_idx, defender_idx);
+        let attacker_strength = group_strength(attacker, cfg);
+        let defender_strength = group_strength(defender, cfg);
+        let total_strength = attacker_strength + defender_strength;
+        let p_attacker_wins = if total_strength > f64::EPSILON {
+            attacker_strength / total_strength
+        } else {
+            0.5
+        };
+
+        outcome.conflicts += 1;
+        if rng.gen::<f64>() < p_attacker_wins {
+            outcome.attacker_wins += 1;
+            settle(attacker, defender, cfg, rng, &mut outcome, flow);
+        } else {
+            settle(defender, attacker, cfg, rng, &mut outcome, flow);
+        }
+    }
+
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    /// A group of four warriors and a group of three members who cannot
+    /// fight, all holding 10 resources.
+    fn rivals<R: Rng + ?Sized>(rng: &mut R) -> Vec<Group> {
+        let cfg = GroupCfg {
+            max_size: 100,
+            fission: FissionPolicy::default(),
+            min_size: 0,
+        };
+        let mut warriors = Group::new(4, AgentCfg::default(), cfg, rng);
+        warriors
+            .members
+            .iter_mut()
+            .for_each(|a| a.warfare_efficiency = 1.0);
+        let mut peaceful = Group::new(3, AgentCfg::default(), cfg, rng);
+        peaceful
+            .members
+            .iter_mut()
+            .for_each(|a| a.warfare_efficiency = 0.0);
+        vec![warriors, peaceful]
+    }
+
+    fn conflict_cfg() -> ConflictCfg {
+        ConflictCfg {
+            raid_probability: 1.0,
+            size_weight: 0.0,
+            warfare_weight: 1.0,
+            hunting_weight: 0.0,
+            casualty_rate: 1.0,
+            loot_fraction: 0.5,
+        }
+    }
+
+    #[test]
+    fn the_stronger_group_wins_loots_and_kills() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(12);
+        let mut groups = rivals(&mut rng);
+        let mut flow = ResourceFlow::default();
+        let outcome = perform_conflicts(&mut groups, &conflict_cfg(), None, &mut rng, &mut flow);
+
+        // Whoever attacks first, the warriors win and the other group is
+        // wiped out; it cannot raid any more
+        assert_eq!(outcome.conflicts, 1);
+        assert_eq!(outcome.casualties, 3);
+        assert_eq!(outcome.resources_looted, 15.0);
+        assert_eq!(flow.lost_with_dead, 15.0);
+        assert!(groups[1].members.is_empty());
+        assert!(groups[0].members.iter().all(|a| a.resources == 13.75));
+    }
+
+    #[test]
+    fn groups_out_of_reach_do_not_fight() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(12);
+        let mut groups = rivals(&mut rng);
+        groups[0].patch = Some(0);
+        groups[1].patch = Some(2);
+        let landscape = Landscape::new(
+            LandscapeCfg {
+                width: 3,
+                height: 1,
+                richness_variation: 0.0,
+                competition_radius: 1,
+                migration_decay: 1.0,
+                clustered_start: false,
+            },
+            &mut rng,
+        );
+        let mut flow = ResourceFlow::default();
+        let outcome = perform_conflicts(
+            &mut groups,
+            &conflict_cfg(),
+            Some(&landscape),
+            &mut rng,
+            &mut flow,
+        );
+        assert_eq!(outcome.conflicts, 0);
+        assert_eq!(groups[1].members.len(), 3);
+    }
+}
@@ -1,12 +1,16 @@
+use rand::seq::SliceRandom;
 use rand::Rng;
 //use rand_distr::{Distribution, Normal};
 //use std::collections::HashSet;
+use std::collections::HashMap;
 
 use crate::simulation::agent::*;
 use crate::simulation::group::*;
 use crate::simulation::memetics::*;
 
 use crate::config::config::*;
+use crate::model::distribution::*;
+use crate::utils::*;
 
 #[derive(Debug, Clone, Copy)]
 pub enum TransferMode {
@@ -14,127 +18,360 @@ pub enum TransferMode {
     Teaching,
 }
 
-fn get_two_mut<T>(slice: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
-    assert!(i != j, "Indices must differ");
-    if i < j {
-        let (left, right) = slice.split_at_mut(j);
-        (&mut left[i], &mut right[0])
-    } else {
-        let (left, right) = slice.split_at_mut(i);
-        (&mut right[0], &mut left[j])
+/// Counts of cultural events that happened to memes of a single kind.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemeEvents {
+    pub inventions: u64,
+    pub transfers: u64,
+    pub vertical_transfers: u64,
+    pub losses: u64,
+    /// Transfers that produced a mutant copy instead of the original meme.
+    pub copy_errors: u64,
+}
+
+/// Cultural events of one simulated year, broken down by meme kind.
+#[derive(Debug, Clone, Default)]
+pub struct CultureLog {
+    pub events: HashMap<usize, MemeEvents>,
+}
+
+impl CultureLog {
+    pub fn kind(&mut self, kind: usize) -> &mut MemeEvents {
+        self.events.entry(kind).or_default()
+    }
+
+    pub fn get(&self, kind: usize) -> MemeEvents {
+        self.events.get(&kind).copied().unwrap_or_default()
     }
 }
 
-pub fn inventions<R: Rng + ?Sized>(group: &mut Group, meme_cfg: &Vec<MemeConfig>, rng: &mut R) {
+/// With `improvement_probability`, picks a random meme of the configured
+/// kind known to the agent, to be improved by the next invention.
+fn improvable_meme<R: Rng + ?Sized>(
+    agent: &Agent,
+    cfg: &SimConfig,
+    config: &MemeConfig,
+    rng: &mut R,
+) -> Option<Meme> {
+    if config.improvement_probability <= 0.0 || rng.gen::<f64>() >= config.improvement_probability {
+        return None;
+    }
+    let kind = cfg.meme_kind_index(&config.meme_kind)?;
+    let known: Vec<&Meme> = agent.memes.iter().filter(|m| m.kind == kind).collect();
+    known.choose(rng).map(|m| **m)
+}
+
+pub fn inventions<R: Rng + ?Sized>(
+    group: &mut Group,
+    cfg: &SimConfig,
+    rng: &mut R,
+    log: &mut CultureLog,
+) {
     group.members.iter_mut().for_each(|agent| {
-        for config in meme_cfg {
+        for config in &cfg.meme_config {
             let roll = rng.gen::<f64>();
             if roll <= config.probability {
-                let meme = Meme::new_typed(
-                    config.meme_kind,
-                    config.size.min,
-                    config.size.max,
-                    config.effect.min,
-                    config.effect.max,
-                    config.size_effect_corr,
-                    rng,
-                );
-                agent.try_learning(meme);
+                let invented = match improvable_meme(agent, cfg, config, rng) {
+                    Some(base) => Some(base.improved(config.improvement_factor, rng)),
+                    None => Meme::from_config(cfg, config, rng),
+                };
+                if let Some(meme) = invented {
+                    if learn(agent, meme, &cfg.memory_config, rng, log) {
+                        log.kind(meme.kind).inventions += 1;
+                    }
+                }
                 break;
             }
         }
     });
 }
 
-pub fn amnesia<R: Rng + ?Sized>(group: &mut Group, rng: &mut R) {
+/// Lets `agent` learn `meme`, first making room for it according to the
+/// memory policy. Memes forgotten to make room count as losses.
+fn learn<R: Rng + ?Sized>(
+    agent: &mut Agent,
+    meme: Meme,
+    cfg: &MemoryCfg,
+    rng: &mut R,
+    log: &mut CultureLog,
+) -> bool {
+    for forgotten in agent.make_room(&meme, cfg.policy, rng) {
+        log.kind(forgotten.kind).losses += 1;
+    }
+    agent.try_learning(meme)
+}
+
+pub fn amnesia<R: Rng + ?Sized>(
+    group: &mut Group,
+    cfg: &SimConfig,
+    rng: &mut R,
+    log: &mut CultureLog,
+) {
+    let memory_cfg = &cfg.memory_config;
+    let rates: Vec<f64> = cfg
+        .meme_kinds
+        .iter()
+        .map(|kind| memory_cfg.forgetting_rate(&kind.name))
+        .collect();
     group.members.iter_mut().for_each(|agent| {
-        agent.memes.retain(|meme| {
+        let age_factor = 1.0 + memory_cfg.age_slope * agent.age as f64;
+        let mut idx = 0;
+        while idx < agent.memes.len() {
             let roll = rng.gen::<f64>();
-            if roll <= 0.02 {
-                match meme.kind {
-                    MemeType::Hunting => {
-                        agent.tot_hunting_efficiency -= meme.effect;
-                    }
-                    MemeType::Learning => {
-                        agent.tot_learning_efficiency -= meme.effect;
-                    }
-                    MemeType::Teaching => {
-                        agent.tot_teaching_efficiency -= meme.effect;
-                    }
-                    MemeType::Trick => {
-                        agent.trick_efficiency -= meme.effect;
-                    }
-                    MemeType::Useless => {
-                        agent.useless_probability -= meme.effect;
-                        if agent.useless_probability < 0.0 {
-                            agent.useless_probability = 0.0;
-                        }
-                    }
+            if roll <= rates[agent.memes[idx].kind] * age_factor {
+                for meme in agent.forget(idx) {
+                    log.kind(meme.kind).losses += 1;
                 }
-                agent.memory_used -= meme.size;
-                agent.meme_id_set.remove(&meme.id);
                 // TODO: decrease counter in meme library & check if the meme disappears forever
-                //println!("Agent {} forgot meme {} of {:?} kind", agent.id, meme.id, meme.kind);
-                return false;
+            } else {
+                idx += 1;
             }
-            return true;
-        });
+        }
     });
 }
 
-pub fn useless<R: Rng + ?Sized>(group: &mut Group, rng: &mut R) {
+pub fn useless<R: Rng + ?Sized>(group: &mut Group, rng: &mut R, flow: &mut ResourceFlow) {
     group.members.iter_mut().for_each(|agent| {
         if agent.useless_probability > 0.0 && agent.resources >= 1.0 {
             let roll = rng.gen::<f64>();
             if roll <= agent.useless_probability {
                 agent.resources -= 1.0;
+                flow.useless += 1.0;
             }
         }
     });
 }
 
+/// How likely each member is to be picked as a partner in one transfer
+/// phase. The weights are computed once at the start of the phase, so that
+/// prestige gained during the phase only counts in the next one.
+enum PartnerWeights {
+    Uniform,
+    /// Running sums of the members' weights.
+    Weighted(Vec<f64>),
+    /// Indices of the parents, children and siblings of every member, who
+    /// get weight `kin_weight` against 1 for everyone else.
+    Kin {
+        kin: Vec<Vec<usize>>,
+        kin_weight: f64,
+    },
+}
+
+impl PartnerWeights {
+    fn new(members: &[Agent], cfg: &TransferCfg) -> Self {
+        let weight = |agent: &Agent| -> f64 {
+            match cfg.partner_selection {
+                PartnerSelection::PrestigeHunting => {
+                    agent.tot_hunting_efficiency.max(0.0).powf(cfg.bias)
+                }
+                PartnerSelection::PrestigeTrick => {
+                    (1.0 + agent.trick_efficiency).max(0.0).powf(cfg.bias)
+                }
+                PartnerSelection::AgeBiased => (1.0 + agent.age as f64).powf(cfg.bias),
+                PartnerSelection::Uniform | PartnerSelection::KinBiased => 1.0,
+            }
+        };
+        match cfg.partner_selection {
+            PartnerSelection::Uniform => PartnerWeights::Uniform,
+            PartnerSelection::KinBiased => PartnerWeights::Kin {
+                kin: kin_indices(members),
+                kin_weight: (1.0 + cfg.bias).max(0.0),
+            },
+            _ => PartnerWeights::Weighted(
+                members
+                    .iter()
+                    .scan(0.0, |sum, agent| {
+                        // invalid weights make a member unpickable
+                        let w = weight(agent);
+                        *sum += if w.is_finite() && w > 0.0 { w } else { 0.0 };
+                        Some(*sum)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Picks the partner of `members[initiator_idx]` for a single interaction.
+    ///
+    /// Returns `None` when no valid partner was drawn; under uniform selection
+    /// the initiator may draw itself, in which case the interaction is skipped.
+    fn choose<R: Rng + ?Sized>(
+        &self,
+        initiator_idx: usize,
+        n: usize,
+        rng: &mut R,
+    ) -> Option<usize> {
+        match self {
+            PartnerWeights::Uniform => {
+                let partner_idx = rng.gen_range(0..n);
+                // skip if same index to avoid dealing with self-learning/teaching
+                (partner_idx != initiator_idx).then_some(partner_idx)
+            }
+            PartnerWeights::Weighted(sums) => {
+                // Draw from the total weight without the initiator's own
+                let before = if initiator_idx > 0 {
+                    sums[initiator_idx - 1]
+                } else {
+                    0.0
+                };
+                let own = sums[initiator_idx] - before;
+                let rest = sums[n - 1] - own;
+                if rest <= 0.0 {
+                    return None;
+                }
+                let mut u = rng.gen::<f64>() * rest;
+                if u >= before {
+                    u += own;
+                }
+                let partner_idx = sums.partition_point(|&sum| sum <= u);
+                (partner_idx < n && partner_idx != initiator_idx).then_some(partner_idx)
+            }
+            PartnerWeights::Kin { kin, kin_weight } => {
+                let kin = &kin[initiator_idx];
+                let kin_total = kin_weight * kin.len() as f64;
+                let others = n - 1 - kin.len();
+                let total = kin_total + others as f64;
+                if total <= 0.0 {
+                    return None;
+                }
+                if rng.gen::<f64>() * total < kin_total {
+                    return kin.choose(rng).copied();
+                }
+                // Some member is neither the initiator nor kin
+                loop {
+                    let partner_idx = rng.gen_range(0..n);
+                    if partner_idx != initiator_idx && !kin.contains(&partner_idx) {
+                        return Some(partner_idx);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indices of the parents, children and siblings (including half-siblings)
+/// of every member, as found by `Agent::is_kin`.
+fn kin_indices(members: &[Agent]) -> Vec<Vec<usize>> {
+    let index: HashMap<usize, usize> = members
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| (agent.id, i))
+        .collect();
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, agent) in members.iter().enumerate() {
+        if let Some((a, b)) = agent.parents {
+            children.entry(a).or_default().push(i);
+            if b != a {
+                children.entry(b).or_default().push(i);
+            }
+        }
+    }
+    let no_one = Vec::new();
+    members
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| {
+            let mut kin: Vec<usize> = children.get(&agent.id).unwrap_or(&no_one).clone();
+            if let Some((a, b)) = agent.parents {
+                for parent in [a, b] {
+                    kin.extend(index.get(&parent));
+                    kin.extend(children.get(&parent).unwrap_or(&no_one));
+                }
+            }
+            kin.sort_unstable();
+            kin.dedup();
+            kin.retain(|&k| k != i);
+            kin
+        })
+        .collect()
+}
+
 pub fn perform_cultural_transfer<R: Rng + ?Sized>(
     group: &mut Group,
     rng: &mut R,
     mode: TransferMode,
+    sim_cfg: &SimConfig,
+    log: &mut CultureLog,
 ) {
+    let cfg = &sim_cfg.transfer_config;
     let n = group.members.len();
     if n < 2 {
         // No interactions possible if there's < 2 members
         return;
     }
 
-    for initiator_idx in 0..n {
-        // Pick a random partner
-        let partner_idx = rng.gen_range(0..n);
-        if partner_idx == initiator_idx {
-            // skip if same index to avoid dealing with self-learning/teaching
-            continue;
-        }
+    let weights = PartnerWeights::new(&group.members, cfg);
+    for _ in 0..cfg.rounds {
+        for initiator_idx in 0..n {
+            let partner_idx = match weights.choose(initiator_idx, n, rng) {
+                Some(idx) => idx,
+                None => continue,
+            };
 
-        // Borrow initiator & partner safely
-        let (initiator, partner) = get_two_mut(&mut group.members, initiator_idx, partner_idx);
+            // Borrow initiator & partner safely
+            let (initiator, partner) = get_two_mut(&mut group.members, initiator_idx, partner_idx);
 
-        match mode {
-            TransferMode::Learning => {
-                // The initiator tries to LEARN from the partner
-                // => Meme must exist in `partner` but not in `initiator`
-                cultural_exchange(
-                    initiator, // "learner"
-                    partner,   // "teacher"
-                    rng, mode,
-                );
+            match mode {
+                TransferMode::Learning => {
+                    // The initiator tries to LEARN from the partner
+                    // => Meme must exist in `partner` but not in `initiator`
+                    cultural_exchange(
+                        initiator, // "learner"
+                        partner,   // "teacher"
+                        rng, mode, sim_cfg, log,
+                    );
+                }
+                TransferMode::Teaching => {
+                    // The initiator tries to TEACH the partner
+                    // => Meme must exist in `initiator` but not in `partner`
+                    cultural_exchange(
+                        initiator, // "teacher"
+                        partner,   // "student"
+                        rng, mode, sim_cfg, log,
+                    );
+                }
             }
-            TransferMode::Teaching => {
-                // The initiator tries to TEACH the partner
-                // => Meme must exist in `initiator` but not in `partner`
-                cultural_exchange(
-                    initiator, // "teacher"
-                    partner,   // "student"
-                    rng, mode,
-                );
+        }
+    }
+}
+
+/// Picks one of the teacher's memes that the student does not know yet.
+fn choose_meme<R: Rng + ?Sized>(
+    teacher: &Agent,
+    student: &Agent,
+    cfg: &TransferCfg,
+    rng: &mut R,
+) -> Option<Meme> {
+    let student_known = &student.meme_id_set;
+    match cfg.meme_choice {
+        MemeChoice::Random => {
+            for _ in 0..cfg.meme_attempts {
+                let meme = teacher.memes[rng.gen_range(0..teacher.memes.len())];
+                if !student_known.contains(&meme.id) {
+                    return Some(meme);
+                }
             }
+            None
         }
+        // Improvements whose prerequisite the student lacks could not be
+        // learned and would block every other meme of the teacher.
+        MemeChoice::MostEffective => teacher
+            .memes
+            .iter()
+            .filter(|meme| !student_known.contains(&meme.id))
+            .filter(|meme| meme.prerequisite.is_none_or(|p| student_known.contains(&p)))
+            .max_by(|a, b| a.effect.total_cmp(&b.effect))
+            .copied(),
+    }
+}
+
+/// The copy of `meme` a learner ends up with: with the configured
+/// probability a cultural mutant, otherwise the meme itself.
+fn copy_meme<R: Rng + ?Sized>(meme: Meme, cfg: &CopyErrorCfg, rng: &mut R) -> Meme {
+    if cfg.probability > 0.0 && rng.gen::<f64>() < cfg.probability {
+        meme.mutated(cfg.effect_std, cfg.size_std, rng)
+    } else {
+        meme
     }
 }
 
@@ -147,7 +384,10 @@ fn cultural_exchange<R: Rng + ?Sized>(
     agent_b: &mut Agent,
     rng: &mut R,
     mode: TransferMode,
+    sim_cfg: &SimConfig,
+    log: &mut CultureLog,
 ) {
+    let cfg = &sim_cfg.transfer_config;
     // Identify which side is the "teacher" vs. "student" for this exchange
     let (teacher, student) = match mode {
         TransferMode::Learning => (agent_b, agent_a), // B->A
@@ -158,35 +398,339 @@ fn cultural_exchange<R: Rng + ?Sized>(
         return;
     }
 
-    let p_success = match mode {
-        TransferMode::Learning => student.tot_learning_efficiency,
-        TransferMode::Teaching => student.tot_learning_efficiency + teacher.tot_teaching_efficiency,
+    let p_success = cfg.success_weight
+        * match mode {
+            TransferMode::Learning => student.tot_learning_efficiency,
+            TransferMode::Teaching => {
+                student.tot_learning_efficiency + teacher.tot_teaching_efficiency
+            }
+        };
+
+    // If there's no new meme to transfer, fail immediately
+    let chosen_meme = match choose_meme(teacher, student, cfg, rng) {
+        Some(meme) => meme,
+        None => return,
     };
-    //let p_success:f64 = 0.1;
+    // The logistic model gives a chance even without any efficiency
+    let p_success =
+        cfg.difficulty
+            .success_probability(p_success, chosen_meme.size, chosen_meme.complexity);
+    if p_success > 0.0 && rng.gen::<f64>() <= p_success {
+        let copy = copy_meme(chosen_meme, &cfg.copy_error, rng);
+        if learn(student, copy, &sim_cfg.memory_config, rng, log) {
+            log.kind(copy.kind).transfers += 1;
+            if copy.id != chosen_meme.id {
+                log.kind(copy.kind).copy_errors += 1;
+            }
+        }
+    }
+}
 
-    if p_success == 0.0 {
+/// Newborns of this year (age 0 with known parents) try to acquire memes
+/// from their parents living in the same group.
+pub fn vertical_transmission<R: Rng + ?Sized>(
+    group: &mut Group,
+    sim_cfg: &SimConfig,
+    rng: &mut R,
+    log: &mut CultureLog,
+) {
+    let cfg = &sim_cfg.vertical_transfer_config;
+    let transfer_cfg = &sim_cfg.transfer_config;
+    if !cfg.enabled {
         return;
     }
-
-    // 1. Which memes does the teacher have that the student doesn't?
-    //let student_known: HashSet<_> = student.culture.memes.iter().map(|m| m.id).collect();
-    let student_known = &student.meme_id_set;
-
-    // If there's no new meme to transfer, fail immediately
-    let possible_memes = &teacher.memes;
-    for _ in 0..3 {
-        // 2. Randomly pick one meme from teacher's memes
-        let chosen_meme = possible_memes[rng.gen_range(0..possible_memes.len())];
-        // If the student doesn't know it, initiate transfer attempt
-        if !student_known.contains(&chosen_meme.id) {
-            if rng.gen::<f64>() <= p_success {
-                let meme_clone = chosen_meme.clone();
-                if student.try_learning(meme_clone) {
-                    // TODO: update meme library
-                    //println!("A meme of {:?} kind has been transferred", chosen_meme.kind);
+    for child_idx in 0..group.members.len() {
+        let (parent_a, parent_b) = match group.members[child_idx].parents {
+            Some(parents) if group.members[child_idx].age == 0 => parents,
+            _ => continue,
+        };
+        for parent_id in [parent_a, parent_b] {
+            let parent_idx = match group.members.iter().position(|a| a.id == parent_id) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let (child, parent) = get_two_mut(&mut group.members, child_idx, parent_idx);
+            if parent.memes.is_empty() {
+                continue;
+            }
+            for _ in 0..cfg.attempts {
+                let meme = match choose_meme(parent, child, transfer_cfg, rng) {
+                    Some(meme) => meme,
+                    None => break,
+                };
+                let p_success = cfg.probability(&sim_cfg.meme_kinds[meme.kind].name)
+                    * match cfg.success {
+                        VerticalSuccess::Fixed => 1.0,
+                        VerticalSuccess::Efficiency => {
+                            child.tot_learning_efficiency + parent.tot_teaching_efficiency
+                        }
+                    };
+                let p_success = transfer_cfg.difficulty.success_probability(
+                    p_success,
+                    meme.size,
+                    meme.complexity,
+                );
+                if rng.gen::<f64>() > p_success {
+                    continue;
+                }
+                let copy = copy_meme(meme, &transfer_cfg.copy_error, rng);
+                if learn(child, copy, &sim_cfg.memory_config, rng, log) {
+                    log.kind(copy.kind).vertical_transfers += 1;
+                    if copy.id != meme.id {
+                        log.kind(copy.kind).copy_errors += 1;
+                    }
                 }
             }
-            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::file::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn meme(id: usize, size: f64, complexity: f64) -> Meme {
+        Meme {
+            id,
+            size,
+            kind: 0,
+            target: EffectTarget::Hunting,
+            effect: 1.0,
+            base_effect: 0.0,
+            complexity,
+            prerequisite: None,
+            depth: 0,
+        }
+    }
+
+    /// Agent with room for `capacity` units of memes who knows `memes`.
+    fn agent_knowing<R: Rng + ?Sized>(capacity: f64, memes: &[Meme], rng: &mut R) -> Agent {
+        let mut agent = Agent::default(rng, AgentCfg::default());
+        agent.mc_alleles = Alleles {
+            allele1: capacity,
+            allele2: capacity,
+        };
+        for &m in memes {
+            assert!(agent.try_learning(m));
+        }
+        agent
+    }
+
+    /// Of `attempts` students without learning efficiency, how many learn
+    /// the teacher's only meme.
+    fn transfers_without_efficiency(cfg: &SimConfig, attempts: usize) -> usize {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(9);
+        let mut log = CultureLog::default();
+        let mut teacher = agent_knowing(5.0, &[meme(1, 0.5, 2.0)], &mut rng);
+        (0..attempts)
+            .filter(|_| {
+                let mut student = agent_knowing(5.0, &[], &mut rng);
+                student.tot_learning_efficiency = 0.0;
+                cultural_exchange(
+                    &mut student,
+                    &mut teacher,
+                    &mut rng,
+                    TransferMode::Learning,
+                    cfg,
+                    &mut log,
+                );
+                student.meme_id_set.contains(&1)
+            })
+            .count()
+    }
+
+    #[test]
+    fn kin_are_found_like_is_kin() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(4);
+        let mut members: Vec<Agent> = (0..7)
+            .map(|_| Agent::default(&mut rng, AgentCfg::default()))
+            .collect();
+        let id = |i: usize| members[i].id;
+        // 0 and 1 have children 2 and 3, 1 and 4 have the half-sibling 5
+        let parents = [
+            (2, (id(0), id(1))),
+            (3, (id(0), id(1))),
+            (5, (id(1), id(4))),
+        ];
+        for (child, pair) in parents {
+            members[child].parents = Some(pair);
+        }
+        let kin = kin_indices(&members);
+        for (i, found) in kin.iter().enumerate() {
+            let expected: Vec<usize> = (0..members.len())
+                .filter(|&j| j != i && members[i].is_kin(&members[j]))
+                .collect();
+            assert_eq!(found, &expected, "kin of {}", i);
+        }
+        assert_eq!(kin[1], [2, 3, 5]);
+        assert!(kin[6].is_empty());
+    }
+
+    #[test]
+    fn partners_are_drawn_by_weight() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(4);
+        let mut members: Vec<Agent> = (0..4)
+            .map(|_| Agent::default(&mut rng, AgentCfg::default()))
+            .collect();
+        for (agent, hunting) in members.iter_mut().zip([0.0, 5.0, 0.0, 15.0]) {
+            agent.tot_hunting_efficiency = hunting;
+        }
+        let cfg = TransferCfg {
+            partner_selection: PartnerSelection::PrestigeHunting,
+            bias: 1.0,
+            ..TransferCfg::default()
+        };
+        let weights = PartnerWeights::new(&members, &cfg);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            counts[weights.choose(0, 4, &mut rng).unwrap()] += 1;
+        }
+        assert_eq!((counts[0], counts[2]), (0, 0));
+        assert!((900..1100).contains(&counts[1]), "{:?}", counts);
+        // The initiator's own weight is left out
+        for _ in 0..100 {
+            assert_eq!(weights.choose(3, 4, &mut rng), Some(1));
+        }
+
+        // The initiator's child is favoured by `1 + bias`
+        let cfg = TransferCfg {
+            partner_selection: PartnerSelection::KinBiased,
+            bias: 3.0,
+            ..TransferCfg::default()
+        };
+        members[1].parents = Some((members[0].id, 12345));
+        let weights = PartnerWeights::new(&members, &cfg);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            counts[weights.choose(0, 4, &mut rng).unwrap()] += 1;
+        }
+        // Weights 4, 1 and 1 for the child and the others
+        assert_eq!(counts[0], 0);
+        assert!((2500..2850).contains(&counts[1]), "{:?}", counts);
+        assert!((550..790).contains(&counts[2]), "{:?}", counts);
+    }
+
+    #[test]
+    fn logistic_difficulty_applies_without_efficiency() {
+        let mut cfg = example_config();
+        assert_eq!(transfers_without_efficiency(&cfg, 200), 0);
+
+        // 1 / (1 + e^-(0 - 0.5 * 2)) = 0.27
+        cfg.transfer_config.difficulty = DifficultyCfg {
+            source: DifficultySource::Complexity,
+            model: DifficultyModel::Logistic {
+                steepness: 1.0,
+                weight: 0.5,
+            },
+        };
+        let learned = transfers_without_efficiency(&cfg, 1000);
+        assert!((220..320).contains(&learned), "{} of 1000", learned);
+    }
+
+    #[test]
+    fn newborns_learn_from_their_parents_only() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(6);
+        let mut cfg = example_config();
+        cfg.vertical_transfer_config = VerticalTransferCfg {
+            enabled: true,
+            attempts: 4,
+            success: VerticalSuccess::Fixed,
+            probabilities: vec![KindProbability {
+                meme_kind: "Learning".to_string(),
+                probability: 0.0,
+            }],
+            default_probability: 1.0,
+        };
+        cfg.transfer_config.meme_choice = MemeChoice::MostEffective;
+        let learning = Meme {
+            kind: 1,
+            effect: 0.5,
+            ..meme(3, 0.5, 0.0)
+        };
+        let mother = agent_knowing(5.0, &[meme(1, 0.5, 0.0)], &mut rng);
+        let father = agent_knowing(5.0, &[meme(2, 0.5, 0.0), learning], &mut rng);
+        let parents = Some((mother.id, father.id));
+        let mut newborn = agent_knowing(5.0, &[], &mut rng);
+        newborn.parents = parents;
+        let mut orphan = agent_knowing(5.0, &[], &mut rng);
+        orphan.age = 0;
+        let mut older = agent_knowing(5.0, &[], &mut rng);
+        older.parents = parents;
+        older.age = 1;
+        let mut group = Group::new(
+            0,
+            AgentCfg::default(),
+            GroupCfg {
+                max_size: 100,
+                fission: FissionPolicy::default(),
+                min_size: 0,
+            },
+            &mut rng,
+        );
+        group.members = vec![mother, father, newborn, orphan, older];
+
+        let mut log = CultureLog::default();
+        vertical_transmission(&mut group, &cfg, &mut rng, &mut log);
+        let known = |agent: &Agent| {
+            let mut ids: Vec<usize> = agent.memes.iter().map(|m| m.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(known(&group.members[2]), vec![1, 2]);
+        assert!(group.members[3..]
+            .iter()
+            .all(|agent| agent.memes.is_empty()));
+        assert_eq!(log.get(0).vertical_transfers, 2);
+        assert_eq!(log.get(1).vertical_transfers, 0);
+
+        cfg.vertical_transfer_config.enabled = false;
+        group.members[2].forget(0);
+        group.members[2].forget(0);
+        vertical_transmission(&mut group, &cfg, &mut rng, &mut log);
+        assert!(group.members[2].memes.is_empty());
+    }
+
+    #[test]
+    fn copy_errors_produce_mutants() {
+        let mut cfg = example_config();
+        let learned_copy = |cfg: &SimConfig, seed: u64| {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+            let mut log = CultureLog::default();
+            let mut teacher = agent_knowing(5.0, &[meme(1, 0.5, 0.0)], &mut rng);
+            let mut student = agent_knowing(5.0, &[], &mut rng);
+            student.tot_learning_efficiency = 1.0;
+            cultural_exchange(
+                &mut student,
+                &mut teacher,
+                &mut rng,
+                TransferMode::Learning,
+                cfg,
+                &mut log,
+            );
+            assert_eq!(student.memes.len(), 1);
+            assert_eq!(log.get(0).transfers, 1);
+            (student.memes[0], log.get(0).copy_errors)
+        };
+
+        cfg.transfer_config.copy_error = CopyErrorCfg {
+            probability: 0.0,
+            effect_std: 0.5,
+            size_std: 0.1,
+        };
+        let (copy, errors) = learned_copy(&cfg, 14);
+        assert_eq!((copy.id, copy.size, copy.effect, errors), (1, 0.5, 1.0, 0));
+
+        cfg.transfer_config.copy_error.probability = 1.0;
+        for seed in 0..20 {
+            let (mutant, errors) = learned_copy(&cfg, seed);
+            assert_ne!(mutant.id, 1);
+            assert_eq!(errors, 1);
+            assert_eq!((mutant.kind, mutant.target), (0, EffectTarget::Hunting));
+            assert!(mutant.size >= 0.01 && mutant.effect >= 0.0);
+            assert!(mutant.size != 0.5 && mutant.effect != 1.0);
         }
     }
 }
@@ -1,27 +1,188 @@
+use crate::config::config::*;
 use crate::simulation::group::*;
+use crate::simulation::landscape::*;
+use crate::utils::*;
 
-pub fn distribute_resources(group: &mut Group, total_resources: f64) {
-    // 1. Calculate the sum of (1 + trick_efficiency) for all agents
-    let denominator: f64 = group
+use rand::Rng;
+
+/// Resources a member pays to take part in the hunt.
+const HUNTING_COST: f64 = 2.0;
+
+/// Share of the yearly resources received by a single group.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupShare {
+    pub group_id: usize,
+    pub patch: Option<usize>,
+    pub members: usize,
+    pub share: f64,
+}
+
+/// Resource bookkeeping for one simulated year.
+///
+/// Every quantity that enters or leaves the agents' holdings is recorded
+/// here, so that `held(year - 1) + distributed` equals the sum of all
+/// spendings and losses plus `held(year)`.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceFlow {
+    pub distributed: f64,
+    pub group_shares: Vec<GroupShare>,
+    /// Gini coefficient of individual resources right after distribution.
+    pub gini: f64,
+    pub hunting: f64,
+    pub food: f64,
+    pub useless: f64,
+    pub reproduction: f64,
+    pub lost_with_dead: f64,
+    /// Resources of agents removed together with dissolved groups.
+    pub lost_with_dissolved: f64,
+    /// Resources migrants lost on the way to their new group.
+    pub lost_in_migration: f64,
+    /// Leftovers overwritten when the new shares are handed out, apart from
+    /// the hunting cost shirkers saved.
+    pub expired: f64,
+}
+
+/// Every member passes `kin_fraction` of its resources on to its relatives,
+/// proportionally to their relatedness. Members without relatives keep it.
+fn share_with_kin(group: &mut Group, kin_fraction: f64) {
+    let n = group.members.len();
+    let mut received = vec![0.0; n];
+    for giver in 0..n {
+        let relatedness: Vec<f64> = group
+            .members
+            .iter()
+            .enumerate()
+            .map(|(i, agent)| {
+                if i == giver {
+                    0.0
+                } else {
+                    group.members[giver].relatedness(agent)
+                }
+            })
+            .collect();
+        let total: f64 = relatedness.iter().sum();
+        if total <= 0.0 {
+            continue;
+        }
+        let gift = kin_fraction * group.members[giver].resources;
+        received[giver] -= gift;
+        for (i, r) in relatedness.iter().enumerate() {
+            received[i] += gift * r / total;
+        }
+    }
+    for (agent, delta) in group.members.iter_mut().zip(received) {
+        agent.resources += delta;
+    }
+}
+
+/// Parents hand `fraction` of their resources to their children younger
+/// than `years`, in equal parts.
+fn provision_offspring(group: &mut Group, years: u8, fraction: f64) {
+    let n = group.members.len();
+    let mut received = vec![0.0; n];
+    for parent in 0..n {
+        let children: Vec<usize> = (0..n)
+            .filter(|&i| {
+                group.members[i].age < years
+                    && group.members[parent].is_parent_of(&group.members[i])
+            })
+            .collect();
+        if children.is_empty() {
+            continue;
+        }
+        let gift = fraction * group.members[parent].resources;
+        received[parent] -= gift;
+        for child in &children {
+            received[*child] += gift / children.len() as f64;
+        }
+    }
+    for (agent, delta) in group.members.iter_mut().zip(received) {
+        agent.resources += delta;
+    }
+}
+
+pub fn distribute_resources(
+    group: &mut Group,
+    total_resources: f64,
+    cfg: &SharingCfg,
+    flow: &mut ResourceFlow,
+) {
+    // 1. Share weights according to the sharing rule, reduced for cheaters
+    let weights: Vec<f64> = group
         .members
         .iter()
-        .map(|agent| 1.0 + agent.trick_efficiency)
-        .sum();
+        .map(|agent| {
+            let weight = match cfg.rule {
+                SharingRule::Egalitarian => 1.0,
+                SharingRule::TrickWeighted | SharingRule::KinWeighted { .. } => {
+                    1.0 + agent.trick_efficiency
+                }
+            };
+            if agent.shirked {
+                weight * (1.0 - cfg.cheater_penalty)
+            } else {
+                weight
+            }
+        })
+        .collect();
+    let denominator: f64 = weights.iter().sum();
+
+    // 2. Distribute resources proportionally, or equally if no one has a
+    // positive weight
+    let n = group.members.len() as f64;
+    for (agent, weight) in group.members.iter_mut().zip(&weights) {
+        let share_ratio = if denominator > 0.0 {
+            weight / denominator
+        } else {
+            1.0 / n
+        };
+        // Shirkers keep the hunting cost they did not pay
+        let saved = if agent.shirked {
+            HUNTING_COST.min(agent.resources)
+        } else {
+            0.0
+        };
+        flow.expired += agent.resources - saved;
+        agent.resources = saved + share_ratio * total_resources;
+    }
 
-    // 2. Distribute resources proportionally
-    for agent in group.members.iter_mut() {
-        let share_ratio = (1.0 + agent.trick_efficiency) / denominator;
-        agent.resources = share_ratio * total_resources;
+    // 3. Transfers within families
+    if let SharingRule::KinWeighted { kin_fraction } = cfg.rule {
+        share_with_kin(group, kin_fraction);
+    }
+    if cfg.provisioning_years > 0 && cfg.provisioning_fraction > 0.0 {
+        provision_offspring(group, cfg.provisioning_years, cfg.provisioning_fraction);
     }
+
+    flow.distributed += total_resources;
+    flow.group_shares.push(GroupShare {
+        group_id: group.id,
+        patch: group.patch,
+        members: group.members.len(),
+        share: total_resources,
+    });
 }
 
-pub fn hunting_effort(group: &mut Group) -> f64 {
+/// Members who can afford it pay the hunting cost and contribute their
+/// hunting efficiency; those past provisioning age may shirk instead.
+pub fn hunting_effort<R: Rng + ?Sized>(
+    group: &mut Group,
+    cfg: &SharingCfg,
+    rng: &mut R,
+    flow: &mut ResourceFlow,
+) -> f64 {
     group
         .members
         .iter_mut()
         .map(|agent| {
-            if agent.resources >= 2.0 {
-                agent.resources -= 2.0;
+            let able = agent.resources >= HUNTING_COST;
+            agent.shirked = able
+                && cfg.shirking_probability > 0.0
+                && agent.age >= cfg.provisioning_years
+                && rng.gen::<f64>() < cfg.shirking_probability;
+            if able && !agent.shirked {
+                agent.resources -= HUNTING_COST;
+                flow.hunting += HUNTING_COST;
                 agent.tot_hunting_efficiency
             } else {
                 0.0
@@ -30,32 +191,257 @@ pub fn hunting_effort(group: &mut Group) -> f64 {
         .sum()
 }
 
-pub fn share_resources_across_groups(groups: &mut Vec<Group>, total_resources: f64) {
+/// Splits each patch's resources between the groups within the competition
+/// radius, proportionally to their hunting efforts. Resources of patches with
+/// no hunters nearby are left unused.
+fn landscape_shares(
+    groups: &[Group],
+    efforts: &[f64],
+    landscape: &Landscape,
+    total_resources: f64,
+) -> Vec<f64> {
+    let radius = landscape.config.competition_radius;
+    let mut shares = vec![0.0; groups.len()];
+    for patch in 0..landscape.patch_count() {
+        let competitors: Vec<usize> = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| {
+                group
+                    .patch
+                    .is_some_and(|p| landscape.distance(p, patch) <= radius)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let local_effort: f64 = competitors.iter().map(|&i| efforts[i]).sum();
+        if local_effort <= f64::EPSILON {
+            continue;
+        }
+        let pool = total_resources * landscape.richness[patch];
+        for i in competitors {
+            shares[i] += pool * efforts[i] / local_effort;
+        }
+    }
+    shares
+}
+
+pub fn share_resources_across_groups<R: Rng + ?Sized>(
+    groups: &mut [Group],
+    total_resources: f64,
+    landscape: Option<&Landscape>,
+    cfg: &SharingCfg,
+    rng: &mut R,
+    flow: &mut ResourceFlow,
+) {
     // 1. Compute the hunting effort for each group
     let efforts: Vec<f64> = groups
         .iter_mut()
-        .map(|group| hunting_effort(group))
+        .map(|group| hunting_effort(group, cfg, rng, flow))
         .collect();
 
-    // 2. Calculate the sum of all efforts
-    let total_effort: f64 = efforts.iter().sum();
+    if let Some(landscape) = landscape {
+        // Hunting competition only happens among neighbours
+        let shares = landscape_shares(groups, &efforts, landscape, total_resources);
+        for (i, group) in groups.iter_mut().enumerate() {
+            distribute_resources(group, shares[i], cfg, flow);
+        }
+    } else {
+        // 2. Calculate the sum of all efforts
+        let total_effort: f64 = efforts.iter().sum();
 
-    // 3. If total_effort is 0, no group gets anything
-    if total_effort <= f64::EPSILON {
-        for group in groups.iter_mut() {
-            distribute_resources(group, 0.0);
+        // 3. If total_effort is 0, no group gets anything
+        if total_effort <= f64::EPSILON {
+            for group in groups.iter_mut() {
+                distribute_resources(group, 0.0, cfg, flow);
+            }
+        } else {
+            // 4. Otherwise, distribute resources proportionally
+            for (i, group) in groups.iter_mut().enumerate() {
+                let proportion = efforts[i] / total_effort;
+                let group_share = proportion * total_resources;
+                distribute_resources(group, group_share, cfg, flow);
+            }
         }
-        return;
     }
 
-    // 4. Otherwise, distribute resources proportionally
-    for (i, group) in groups.iter_mut().enumerate() {
-        let proportion = efforts[i] / total_effort;
-        let group_share = proportion * total_resources;
-        distribute_resources(group, group_share);
-    }
+    let mut holdings: Vec<f64> = groups
+        .iter()
+        .flat_map(|group| group.members.iter().map(|agent| agent.resources))
+        .collect();
+    flow.gini = gini_coefficient(&mut holdings);
 }
 
-pub fn clean_up_groups(groups: &mut Vec<Group>) {
-    groups.retain(|group| group.members.len() > 1);
+pub fn clean_up_groups(groups: &mut Vec<Group>, flow: &mut ResourceFlow) {
+    groups.retain(|group| {
+        if group.members.len() > 1 {
+            return true;
+        }
+        flow.lost_with_dissolved += group.members.iter().map(|a| a.resources).sum::<f64>();
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::culture::*;
+    use crate::model::population::*;
+    use crate::model::reproduction::*;
+    use crate::simulation::agent::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn group_of<R: Rng + ?Sized>(ages: &[u8], rng: &mut R) -> Group {
+        let cfg = GroupCfg {
+            max_size: 100,
+            fission: FissionPolicy::default(),
+            min_size: 0,
+        };
+        let mut group = Group::new(ages.len(), AgentCfg::default(), cfg, rng);
+        for (agent, &age) in group.members.iter_mut().zip(ages) {
+            agent.age = age;
+        }
+        group
+    }
+
+    #[test]
+    fn shirkers_keep_the_hunting_cost() {
+        // The young member hunts, the adult one always shirks
+        let share = |cheater_penalty: f64| {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(5);
+            let mut groups = vec![group_of(&[2, 20], &mut rng)];
+            let cfg = SharingCfg {
+                rule: SharingRule::Egalitarian,
+                provisioning_years: 5,
+                provisioning_fraction: 0.0,
+                shirking_probability: 1.0,
+                cheater_penalty,
+            };
+            let mut flow = ResourceFlow::default();
+            share_resources_across_groups(&mut groups, 100.0, None, &cfg, &mut rng, &mut flow);
+            let members: Vec<&Agent> = groups[0].members.iter().collect();
+            assert!(!members[0].shirked && members[1].shirked);
+            assert_eq!(flow.hunting, HUNTING_COST);
+            // Both started with 10; the hunter's 8 and the shirker's 8 expire
+            assert_eq!(flow.expired, 16.0);
+            (members[0].resources, members[1].resources)
+        };
+
+        let (hunter, shirker) = share(0.0);
+        assert_eq!((hunter, shirker), (50.0, 52.0));
+        let (hunter, shirker) = share(1.0);
+        assert_eq!((hunter, shirker), (100.0, 2.0));
+    }
+
+    #[test]
+    fn patches_are_shared_between_nearby_hunters() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(2);
+        let cfg = LandscapeCfg {
+            width: 3,
+            height: 1,
+            richness_variation: 0.5,
+            competition_radius: 0,
+            migration_decay: 1.0,
+            clustered_start: false,
+        };
+        let random = Landscape::new(cfg, &mut rng);
+        assert!((random.richness.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        let mut landscape = Landscape {
+            richness: vec![0.5, 0.3, 0.2],
+            ..random
+        };
+        let mut groups = vec![
+            group_of(&[20], &mut rng),
+            group_of(&[20], &mut rng),
+            group_of(&[20], &mut rng),
+        ];
+        for (group, patch) in groups.iter_mut().zip([0, 0, 2]) {
+            group.patch = Some(patch);
+        }
+        let efforts = [1.0, 3.0, 2.0];
+
+        // The middle patch has no hunters on it and goes unused
+        let shares = landscape_shares(&groups, &efforts, &landscape, 100.0);
+        assert_eq!(shares, vec![12.5, 37.5, 20.0]);
+
+        // Everyone reaches the middle patch, the groups on the left do not
+        // reach the right one
+        landscape.config.competition_radius = 1;
+        let shares = landscape_shares(&groups, &efforts, &landscape, 100.0);
+        assert_eq!(shares, vec![17.5, 52.5, 30.0]);
+    }
+
+    #[test]
+    fn a_year_of_resource_flow_balances() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(11);
+        let mut groups = vec![
+            group_of(&[1, 3, 8, 12, 20, 25, 30, 40], &mut rng),
+            group_of(&[2, 7, 9, 15, 22, 35], &mut rng),
+            group_of(&[18], &mut rng),
+        ];
+        let sharing = SharingCfg {
+            rule: SharingRule::TrickWeighted,
+            provisioning_years: 5,
+            provisioning_fraction: 0.3,
+            shirking_probability: 0.3,
+            cheater_penalty: 0.5,
+        };
+        let migration = MigrationCfg {
+            rate: 0.5,
+            resource_cost: 0.2,
+            ..MigrationCfg::default()
+        };
+        let held = |groups: &[Group]| -> f64 {
+            groups
+                .iter()
+                .flat_map(|group| group.members.iter().map(|agent| agent.resources))
+                .sum()
+        };
+
+        // The resource phases of a year, in the order of the simulation loop
+        let before = held(&groups);
+        let mut flow = ResourceFlow::default();
+        let mut log = CultureLog::default();
+        groups
+            .iter_mut()
+            .for_each(|group| dinner_time(group, &mut flow));
+        share_resources_across_groups(&mut groups, 1000.0, None, &sharing, &mut rng, &mut flow);
+        groups.iter_mut().for_each(|group| {
+            useless(group, &mut rng, &mut flow);
+            grim_reaper(group, &MortalityCfg::default(), &mut rng, &mut flow);
+        });
+        clean_up_groups(&mut groups, &mut flow);
+        let mut reproduction = ReproductionOutcome::default();
+        groups.iter_mut().for_each(|group| {
+            reproduction.merge(&reproduce_group(
+                group,
+                &mut rng,
+                crate::config::file::example_config().mutation_config,
+                &MatingCfg::default(),
+            ));
+        });
+        perform_migration(&mut groups, &mut rng, &migration, None, &mut log, &mut flow);
+        let after = held(&groups);
+
+        assert_eq!(groups.len(), 2);
+        assert!(flow.hunting > 0.0 && flow.expired > 0.0 && flow.lost_with_dissolved > 0.0);
+        assert!(reproduction.births > 0 && flow.lost_in_migration > 0.0);
+        let spent = flow.hunting
+            + flow.food
+            + flow.useless
+            + reproduction.resources_spent
+            + flow.lost_with_dead
+            + flow.lost_with_dissolved
+            + flow.lost_in_migration
+            + flow.expired;
+        assert!(
+            (before + flow.distributed - spent - after).abs() < 1e-9,
+            "{} + {} != {} + {}",
+            before,
+            flow.distributed,
+            spent,
+            after
+        );
+    }
 }
@@ -1,3 +1,4 @@
+pub mod conflict;
 pub mod culture;
 pub mod distribution;
 pub mod population;
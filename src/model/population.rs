@@ -1,35 +1,125 @@
+use crate::config::config::*;
+use crate::config::file::*;
+use crate::model::culture::*;
+use crate::model::distribution::*;
+use crate::simulation::agent::*;
 use crate::simulation::group::*;
+use crate::simulation::landscape::*;
+use crate::simulation::memetics::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
 use rand::Rng;
+use std::collections::HashMap;
 
-pub fn dinner_time(group: &mut Group) {
+/// Draws both copies of an allele and clamps them to `[min, max]`.
+fn founder_alleles<R: Rng + ?Sized>(
+    distribution: &ValueDistribution,
+    min: f64,
+    max: f64,
+    rng: &mut R,
+) -> Alleles {
+    Alleles {
+        allele1: distribution.sample(rng).clamp(min, max),
+        allele2: distribution.sample(rng).clamp(min, max),
+    }
+}
+
+fn founder<R: Rng + ?Sized>(cfg: &SimConfig, rng: &mut R) -> Agent {
+    let population = &cfg.population_config;
+    let mc_alleles = founder_alleles(&population.mc_allele, 0.0, f64::INFINITY, rng);
+    let le_alleles = founder_alleles(&population.le_allele, 0.0, 1.0, rng);
+    let te_alleles = founder_alleles(&population.te_allele, 0.0, 1.0, rng);
+    let mut agent = Agent::newborn(rng, mc_alleles, le_alleles, te_alleles, cfg.agent_config);
+    if cfg.mating_config.sexes {
+        agent.sex = Sex::random(rng);
+    }
+    agent.resources = population.resources.sample(rng).max(0.0);
+    agent.age = population
+        .age
+        .sample(rng)
+        .round()
+        .clamp(0.0, u8::MAX as f64) as u8;
+    for _ in 0..population.starting_memes {
+        if let Some(meme) = Meme::new_random(cfg, rng) {
+            agent.try_learning(meme);
+        }
+    }
+    agent
+}
+
+/// Founder groups of a new run, loaded from the configured snapshot or
+/// drawn as described by the population config.
+pub fn founding_groups<R: Rng + ?Sized>(
+    cfg: &SimConfig,
+    rng: &mut R,
+) -> std::io::Result<Vec<Group>> {
+    let population = &cfg.population_config;
+    if let Some(path) = &population.snapshot {
+        let mut groups = load_snapshot(path, cfg)?;
+        // Snapshots of runs without sexes have none to keep
+        if cfg.mating_config.sexes {
+            for agent in groups.iter_mut().flat_map(|g| g.members.iter_mut()) {
+                if agent.sex == Sex::Unspecified {
+                    agent.sex = Sex::random(rng);
+                }
+            }
+        }
+        return Ok(groups);
+    }
+    let group_sizes = if population.group_sizes.is_empty() {
+        vec![(cfg.resources as usize) / 17 / 3; 3]
+    } else {
+        population.group_sizes.clone()
+    };
+    Ok(group_sizes
+        .into_iter()
+        .map(|size| {
+            let members: Vec<Agent> = (0..size).map(|_| founder(cfg, rng)).collect();
+            Group::splinter(&members, cfg.group_config, rng)
+        })
+        .collect())
+}
+
+pub fn dinner_time(group: &mut Group, flow: &mut ResourceFlow) {
     // TODO: make the appetites configurable.
     for agent in group.members.iter_mut() {
-        if agent.resources >= 3.0 {
-            agent.resources -= 3.0;
+        let appetite = (3.0 - agent.resource_cost_modifier).max(0.0);
+        if agent.resources >= appetite {
+            agent.resources -= appetite;
+            flow.food += appetite;
             agent.hunger_counter = 0;
         } else {
+            flow.food += agent.resources;
             agent.resources = 0.0;
-            agent.hunger_counter += 1;
+            agent.hunger_counter = agent.hunger_counter.saturating_add(1);
         }
     }
 }
 
 pub fn happy_new_year(group: &mut Group) {
     for agent in group.members.iter_mut() {
-        agent.age += 1;
+        agent.age = agent.age.saturating_add(1);
     }
 }
 
-pub fn grim_reaper<R: Rng + ?Sized>(group: &mut Group, rng: &mut R) -> usize {
+pub fn grim_reaper<R: Rng + ?Sized>(
+    group: &mut Group,
+    mortality: &MortalityCfg,
+    rng: &mut R,
+    flow: &mut ResourceFlow,
+) -> usize {
     let before_count = group.members.len();
     group.members.retain(|agent| {
-        if agent.hunger_counter >= 2 {
+        if agent.hunger_counter >= mortality.starvation_years {
+            flow.lost_with_dead += agent.resources;
             return false;
         }
 
-        let p_death = agent.config.death_prob_multiplier * (agent.age as f64);
+        let p_death = mortality.model.death_probability(agent.age, &agent.config)
+            * (1.0 - agent.mortality_modifier).max(0.0);
         let roll = rng.gen::<f64>();
         if roll < p_death {
+            flow.lost_with_dead += agent.resources;
             return false;
         }
 
@@ -40,85 +130,245 @@ pub fn grim_reaper<R: Rng + ?Sized>(group: &mut Group, rng: &mut R) -> usize {
     before_count - after_count
 }
 
-pub fn perform_migration<R: Rng + ?Sized>(
-    groups: &mut Vec<Group>,
+/// Summary of one year's migration.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MigrationOutcome {
+    pub migrants: u64,
+    /// Sum of the migrants' ages.
+    pub migrant_age: u64,
+    /// Memes that arrived with the migrants.
+    pub memes_carried: u64,
+    /// Memes forgotten on the way.
+    pub memes_lost: u64,
+    pub resources_lost: f64,
+}
+
+/// Yearly probability of `agent` to leave its group.
+fn emigration_probability(agent: &Agent, cfg: &MigrationCfg) -> f64 {
+    let sex_factor = match agent.sex {
+        Sex::Female => cfg.female_factor,
+        Sex::Male => cfg.male_factor,
+        Sex::Unspecified => 1.0,
+    };
+    (cfg.rate * (1.0 + cfg.age_slope * agent.age as f64) * sex_factor).max(0.0)
+}
+
+/// Picks the destination of an agent leaving group `from`. Groups are
+/// weighted according to the configured destination choice. On a landscape
+/// the weight also decays with the distance between the patches, and the
+/// attraction lost to that decay goes to staying, so that distant groups
+/// lower the overall chance of migrating. Returns `None` if the agent stays.
+fn migration_destination<R: Rng + ?Sized>(
+    groups: &[Group],
+    from: usize,
+    cfg: &MigrationCfg,
+    landscape: Option<&Landscape>,
+    rng: &mut R,
+) -> Option<usize> {
+    let n = groups.len();
+    let mut stay = 0.0;
+    let mut weights: Vec<f64> = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            if i == from {
+                return 0.0;
+            }
+            let attraction = match cfg.destination {
+                MigrationDestination::Uniform => 1.0,
+                MigrationDestination::GroupSize => group.members.len() as f64,
+                MigrationDestination::ResourcesPerCapita if group.members.is_empty() => 0.0,
+                MigrationDestination::ResourcesPerCapita => {
+                    group.members.iter().map(|a| a.resources).sum::<f64>()
+                        / group.members.len() as f64
+                }
+            };
+            let decay = match landscape {
+                Some(landscape) => {
+                    let origin = groups[from].patch.unwrap_or(0);
+                    let distance = landscape.distance(origin, group.patch.unwrap_or(0)) as f64;
+                    (-landscape.config.migration_decay * distance).exp()
+                }
+                None => 1.0,
+            };
+            stay += attraction * (1.0 - decay);
+            attraction * decay
+        })
+        .collect();
+    // The origin's own slot is unused as a destination, so it holds the
+    // weight of staying.
+    weights[from] = stay;
+    if let Ok(dist) = WeightedIndex::new(&weights) {
+        let choice = dist.sample(rng);
+        return (choice != from).then_some(choice);
+    }
+
+    let mut new_group_idx = rng.gen_range(0..n);
+    while new_group_idx == from {
+        new_group_idx = rng.gen_range(0..n);
+    }
+    Some(new_group_idx)
+}
+
+/// Takes the migration costs from an agent on its way to a new group.
+fn pay_migration_cost<R: Rng + ?Sized>(
+    agent: &mut Agent,
+    cfg: &MigrationCfg,
     rng: &mut R,
-    migration_prob: f64,
+    outcome: &mut MigrationOutcome,
+    log: &mut CultureLog,
+    flow: &mut ResourceFlow,
 ) {
+    let cost = cfg.resource_cost.clamp(0.0, 1.0) * agent.resources;
+    agent.resources -= cost;
+    outcome.resources_lost += cost;
+    flow.lost_in_migration += cost;
+
+    if cfg.meme_loss_probability > 0.0 {
+        let mut idx = 0;
+        while idx < agent.memes.len() {
+            if rng.gen::<f64>() < cfg.meme_loss_probability {
+                for meme in agent.forget(idx) {
+                    log.kind(meme.kind).losses += 1;
+                    outcome.memes_lost += 1;
+                }
+            } else {
+                idx += 1;
+            }
+        }
+    }
+    outcome.memes_carried += agent.memes.len() as u64;
+}
+
+pub fn perform_migration<R: Rng + ?Sized>(
+    groups: &mut [Group],
+    rng: &mut R,
+    cfg: &MigrationCfg,
+    landscape: Option<&Landscape>,
+    log: &mut CultureLog,
+    flow: &mut ResourceFlow,
+) -> MigrationOutcome {
+    let mut outcome = MigrationOutcome::default();
     let n = groups.len();
 
     // If there's only one group, there's nowhere to migrate
     // so we skip.
     if n < 2 {
-        return;
+        return outcome;
     }
 
-    // For each group in the vector...
-    for i in 0..n {
-        // We'll iterate the group's members from the front,
-        // but each time an agent migrates, we `swap_remove`
-        // them, so we stay at the same index to examine
-        // the newly swapped-in member.
+    // Emigrants of all groups are collected first, so that agents who just
+    // arrived in a group do not move on, and pay again, in the same year.
+    let mut emigrants = Vec::new();
+    for (i, group) in groups.iter_mut().enumerate() {
         let mut j = 0;
-        while j < groups[i].members.len() {
+        while j < group.members.len() {
             let roll = rng.gen::<f64>();
-            if roll < migration_prob {
-                // This agent migrates.
-                // 1) Remove it from group i:
-                let agent = groups[i].members.swap_remove(j);
-
-                // 2) Choose a random group different from i
-                let mut new_group_idx = rng.gen_range(0..n);
-                while new_group_idx == i {
-                    new_group_idx = rng.gen_range(0..n);
-                }
-
-                // 3) Push agent into the new group
-                groups[new_group_idx].members.push(agent);
-
-                // We do *not* increment j here, because swap_remove
-                // has already pulled a new member into index j,
-                // so we want to examine that one next iteration.
+            if roll < emigration_probability(&group.members[j], cfg) {
+                // swap_remove pulls a new member into index j, so we stay
+                // at the same index to examine that one next iteration.
+                emigrants.push((i, group.members.swap_remove(j)));
             } else {
-                // This agent does not migrate, so move on
                 j += 1;
             }
         }
     }
+
+    // Destinations are chosen among the groups as they are once everyone
+    // who considers leaving has left.
+    let destinations: Vec<Option<usize>> = emigrants
+        .iter()
+        .map(|&(i, _)| migration_destination(groups, i, cfg, landscape, rng))
+        .collect();
+    for ((i, mut agent), destination) in emigrants.into_iter().zip(destinations) {
+        // Choose a group different from i, or stay after all
+        let Some(new_group_idx) = destination else {
+            groups[i].members.push(agent);
+            continue;
+        };
+        pay_migration_cost(&mut agent, cfg, rng, &mut outcome, log, flow);
+        outcome.migrants += 1;
+        outcome.migrant_age += agent.age as u64;
+        groups[new_group_idx].members.push(agent);
+    }
+    outcome
 }
 
-pub fn handle_group_splitting<R: Rng + ?Sized>(groups: &mut Vec<Group>, rng: &mut R) {
+/// Splits the members of an oversized group in two according to `policy`.
+/// The first part stays in place, the second one leaves.
+fn fission<R: Rng + ?Sized>(
+    mut members: Vec<Agent>,
+    policy: FissionPolicy,
+    rng: &mut R,
+) -> (Vec<Agent>, Vec<Agent>) {
+    match policy {
+        FissionPolicy::Random => members.into_iter().partition(|_| rng.gen::<f64>() < 0.5),
+        FissionPolicy::KinCohesive => {
+            let mut sibships: HashMap<(usize, usize), Vec<Agent>> = HashMap::new();
+            let mut units = Vec::new();
+            for agent in members {
+                match agent.parents {
+                    Some(parents) => sibships.entry(parents).or_default().push(agent),
+                    None => units.push(vec![agent]),
+                }
+            }
+            units.extend(sibships.into_values());
+            units.shuffle(rng);
+            units.sort_by_key(|unit| std::cmp::Reverse(unit.len()));
+            let (mut stay, mut leave) = (Vec::new(), Vec::new());
+            for unit in units {
+                if stay.len() <= leave.len() {
+                    stay.extend(unit);
+                } else {
+                    leave.extend(unit);
+                }
+            }
+            (stay, leave)
+        }
+        FissionPolicy::TraitAssorted { by } => {
+            let value = |agent: &Agent| match by {
+                AssortTrait::BrainVolume => agent.get_brain_volume(),
+                AssortTrait::MemesKnown => agent.memes.len() as f64,
+            };
+            members.shuffle(rng);
+            members.sort_by(|a, b| value(a).total_cmp(&value(b)));
+            let leave = members.split_off(members.len() / 2);
+            (members, leave)
+        }
+        FissionPolicy::Splinter { fraction } => {
+            members.shuffle(rng);
+            // at least one member leaves and at least one stays
+            let leaving = ((fraction * members.len() as f64).round() as usize)
+                .clamp(1, members.len().saturating_sub(1).max(1));
+            let leave = members.split_off(members.len() - leaving);
+            (members, leave)
+        }
+    }
+}
+
+pub fn handle_group_splitting<R: Rng + ?Sized>(
+    groups: &mut Vec<Group>,
+    rng: &mut R,
+    landscape: Option<&Landscape>,
+) {
     // We'll build a new list of groups
     // that replaces the old list in-place when done.
     let mut new_groups = Vec::new();
 
     // Drain the existing groups so we can consume them one by one
-    for mut group in groups.drain(..) {
+    for group in groups.drain(..) {
         // If group is over capacity, split it
         if group.members.len() > group.config.max_size {
-            /*let mut group_a = Group {
-                id: new_id(rng),      // new ID
-                members: Vec::new(),  // will distribute half the members here
-                config: group.config, // same config
+            let (stay, leave) = fission(group.members, group.config.fission, rng);
+            let mut group_a = Group::splinter(&stay, group.config, rng);
+            let mut group_b = Group::splinter(&leave, group.config, rng);
+
+            // One daughter group stays, the other settles on an adjacent patch
+            group_a.patch = group.patch;
+            group_b.patch = match (landscape, group.patch) {
+                (Some(landscape), Some(patch)) => Some(landscape.random_neighbour(patch, rng)),
+                _ => group.patch,
             };
-            let mut group_b = Group {
-                id: new_id(rng),
-                members: Vec::new(),
-                config: group.config,
-            };*/
-            // pub fn splinter<R: Rng + ?Sized>(members: &Vec<Agent>, group_cfg: GroupCfg, rng: &mut R)
-            let mut group_a = Group::splinter(&Vec::new(), group.config, rng);
-            let mut group_b = Group::splinter(&Vec::new(), group.config, rng);
-
-            // Randomly distribute each member of the old group
-            // with equal probability between the two new groups
-            for agent in group.members.drain(..) {
-                if rng.gen::<f64>() < 0.5 {
-                    group_a.members.push(agent);
-                } else {
-                    group_b.members.push(agent);
-                }
-            }
 
             // Now we have two new subgroups
             new_groups.push(group_a);
@@ -132,3 +382,262 @@ pub fn handle_group_splitting<R: Rng + ?Sized>(groups: &mut Vec<Group>, rng: &mu
     // Replace the old list of groups
     *groups = new_groups;
 }
+
+/// Merges groups smaller than `min_size` into another group, preferring the
+/// nearest ones on the landscape. The last remaining group is never fused.
+pub fn fuse_small_groups<R: Rng + ?Sized>(
+    groups: &mut Vec<Group>,
+    rng: &mut R,
+    landscape: Option<&Landscape>,
+) {
+    while groups.len() > 1 {
+        let small = match groups
+            .iter()
+            .position(|group| group.members.len() < group.config.min_size)
+        {
+            Some(idx) => idx,
+            None => break,
+        };
+        let group = groups.swap_remove(small);
+        let distance = |target: &Group| match (landscape, group.patch, target.patch) {
+            (Some(landscape), Some(a), Some(b)) => landscape.distance(a, b),
+            _ => 0,
+        };
+        let nearest = groups.iter().map(distance).min().unwrap_or(0);
+        let candidates: Vec<usize> = (0..groups.len())
+            .filter(|&i| distance(&groups[i]) == nearest)
+            .collect();
+        let target = *candidates.choose(rng).expect("at least one group remains");
+        groups[target].members.extend(group.members);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn group_cfg() -> GroupCfg {
+        GroupCfg {
+            max_size: 100,
+            fission: FissionPolicy::default(),
+            min_size: 0,
+        }
+    }
+
+    fn groups_of<R: Rng + ?Sized>(sizes: &[usize], rng: &mut R) -> Vec<Group> {
+        sizes
+            .iter()
+            .map(|&size| Group::new(size, AgentCfg::default(), group_cfg(), rng))
+            .collect()
+    }
+
+    #[test]
+    fn migrants_move_and_pay_once_a_year() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(3);
+        let mut groups = groups_of(&[10, 10, 10], &mut rng);
+        let origin: HashMap<usize, usize> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(i, group)| group.members.iter().map(move |agent| (agent.id, i)))
+            .collect();
+        let cfg = MigrationCfg {
+            rate: 1.0,
+            resource_cost: 0.5,
+            ..MigrationCfg::default()
+        };
+        let mut flow = ResourceFlow::default();
+        let outcome = perform_migration(
+            &mut groups,
+            &mut rng,
+            &cfg,
+            None,
+            &mut CultureLog::default(),
+            &mut flow,
+        );
+
+        assert_eq!(outcome.migrants, 30);
+        assert!((outcome.resources_lost - 150.0).abs() < 1e-9);
+        assert!((flow.lost_in_migration - 150.0).abs() < 1e-9);
+        for (i, group) in groups.iter().enumerate() {
+            for agent in &group.members {
+                assert_ne!(origin[&agent.id], i);
+                assert_eq!(agent.resources, 5.0);
+            }
+        }
+        let headcount: usize = groups.iter().map(|g| g.members.len()).sum();
+        assert_eq!(headcount, 30);
+    }
+
+    #[test]
+    fn fission_policies_split_as_configured() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(8);
+        let members = |n: usize, rng: &mut Xoshiro256PlusPlus| -> Vec<Agent> {
+            let mut members = groups_of(&[n], rng).remove(0).members;
+            for (i, agent) in members.iter_mut().enumerate() {
+                agent.mc_alleles = Alleles {
+                    allele1: i as f64,
+                    allele2: i as f64,
+                };
+            }
+            members
+        };
+        let sizes = |(stay, leave): (Vec<Agent>, Vec<Agent>)| (stay.len(), leave.len());
+
+        let splinter = |fraction| FissionPolicy::Splinter { fraction };
+        assert_eq!(
+            sizes(fission(members(12, &mut rng), splinter(0.25), &mut rng)),
+            (9, 3)
+        );
+        assert_eq!(
+            sizes(fission(members(4, &mut rng), splinter(0.01), &mut rng)),
+            (3, 1)
+        );
+        assert_eq!(
+            sizes(fission(members(4, &mut rng), splinter(0.99), &mut rng)),
+            (1, 3)
+        );
+
+        let by_brain = FissionPolicy::TraitAssorted {
+            by: AssortTrait::BrainVolume,
+        };
+        let (small, large) = fission(members(9, &mut rng), by_brain, &mut rng);
+        assert_eq!((small.len(), large.len()), (4, 5));
+        let largest_small = small
+            .iter()
+            .map(|a| a.get_brain_volume())
+            .fold(0.0, f64::max);
+        assert!(large.iter().all(|a| a.get_brain_volume() > largest_small));
+
+        // Three full sibships of 3, 2 and 2 and three members without parents
+        let mut kin = members(10, &mut rng);
+        for (i, agent) in kin.iter_mut().enumerate().take(7) {
+            agent.parents = Some(match i {
+                0..=2 => (100, 101),
+                3 | 4 => (102, 103),
+                _ => (104, 105),
+            });
+        }
+        let (stay, leave) = fission(kin, FissionPolicy::KinCohesive, &mut rng);
+        assert_eq!((stay.len(), leave.len()), (5, 5));
+        for parents in [(100, 101), (102, 103), (104, 105)] {
+            let stayed = stay.iter().filter(|a| a.parents == Some(parents)).count();
+            let left = leave.iter().filter(|a| a.parents == Some(parents)).count();
+            assert!(stayed == 0 || left == 0, "{:?} was split", parents);
+        }
+    }
+
+    #[test]
+    fn oversized_groups_split_and_small_ones_fuse() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(10);
+        let cfg = GroupCfg {
+            max_size: 10,
+            fission: FissionPolicy::Splinter { fraction: 0.5 },
+            min_size: 4,
+        };
+        let mut groups: Vec<Group> = [12, 10, 2, 3]
+            .iter()
+            .map(|&size| Group::new(size, AgentCfg::default(), cfg, &mut rng))
+            .collect();
+
+        handle_group_splitting(&mut groups, &mut rng, None);
+        let mut sizes: Vec<usize> = groups.iter().map(|g| g.members.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 3, 6, 6, 10]);
+
+        // On a line of patches the small groups join their neighbours
+        let landscape = Landscape {
+            width: 5,
+            height: 1,
+            richness: vec![0.2; 5],
+            config: LandscapeCfg {
+                width: 5,
+                height: 1,
+                richness_variation: 0.0,
+                competition_radius: 0,
+                migration_decay: 1.0,
+                clustered_start: false,
+            },
+        };
+        groups.sort_by_key(|g| g.members.len());
+        for (group, patch) in groups.iter_mut().zip([0, 4, 1, 2, 3]) {
+            group.patch = Some(patch);
+        }
+        fuse_small_groups(&mut groups, &mut rng, Some(&landscape));
+        let mut fused: Vec<(usize, usize)> = groups
+            .iter()
+            .map(|g| (g.patch.unwrap(), g.members.len()))
+            .collect();
+        fused.sort();
+        assert_eq!(fused, vec![(1, 8), (2, 6), (3, 13)]);
+
+        // The last group is never fused, however small
+        let mut last = vec![Group::new(1, AgentCfg::default(), cfg, &mut rng)];
+        fuse_small_groups(&mut last, &mut rng, None);
+        assert_eq!(last.len(), 1);
+    }
+
+    #[test]
+    fn founders_follow_the_population_config_and_snapshots() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(15);
+        let mut cfg = example_config();
+        cfg.population_config = PopulationCfg {
+            group_sizes: vec![5, 7],
+            mc_allele: ValueDistribution::Uniform { min: 2.0, max: 4.0 },
+            le_allele: ValueDistribution::Normal {
+                mean: 2.0,
+                std: 0.1,
+            },
+            te_allele: ValueDistribution::Fixed { value: 0.3 },
+            starting_memes: 3,
+            resources: ValueDistribution::Fixed { value: 25.0 },
+            age: ValueDistribution::Uniform {
+                min: 10.0,
+                max: 20.0,
+            },
+            snapshot: None,
+        };
+        let groups = founding_groups(&cfg, &mut rng).unwrap();
+        let sizes: Vec<usize> = groups.iter().map(|g| g.members.len()).collect();
+        assert_eq!(sizes, vec![5, 7]);
+        let founders: Vec<&Agent> = groups.iter().flat_map(|g| &g.members).collect();
+        for agent in &founders {
+            let mc = agent.mc_alleles;
+            assert!((2.0..4.0).contains(&mc.allele1) && (2.0..4.0).contains(&mc.allele2));
+            // drawn around 2 and clamped like mutated alleles
+            assert_eq!(
+                (agent.le_alleles.allele1, agent.le_alleles.allele2),
+                (1.0, 1.0)
+            );
+            assert_eq!(agent.te_alleles.allele1, 0.3);
+            assert_eq!(agent.resources, 25.0);
+            assert!((10..=20).contains(&agent.age));
+            assert!(!agent.memes.is_empty() && agent.memes.len() <= 3);
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("tribesim-snapshot-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        save_snapshot(&path, &groups).unwrap();
+        cfg.population_config.snapshot = Some(path.clone());
+        let loaded = founding_groups(&cfg, &mut rng).unwrap();
+        let summary = |groups: &[Group]| -> Vec<(usize, usize, f64, u8, Vec<usize>)> {
+            groups
+                .iter()
+                .flat_map(|g| g.members.iter().map(move |a| (g.id, a)))
+                .map(|(group, a)| {
+                    let memes = a.memes.iter().map(|m| m.id).collect();
+                    (group, a.id, a.resources, a.age, memes)
+                })
+                .collect()
+        };
+        assert_eq!(summary(&loaded), summary(&groups));
+
+        // Memes of kinds the new config does not declare are rejected
+        cfg.meme_kinds.truncate(0);
+        let error = founding_groups(&cfg, &mut rng).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,15 +2,57 @@ use crate::simulation::agent::*;
 use crate::simulation::group::*;
 
 use crate::config::config::*;
+use crate::utils::*;
 
+use rand::distributions::WeightedIndex;
 use rand::prelude::*;
 use rand::Rng;
+use std::collections::HashSet;
+
+/// Summary of a reproduction phase: how many pairs were formed, how many of
+/// them produced a child and where the resources went.
+///
+/// Sums are kept rather than means so that outcomes of several groups can be
+/// merged before the yearly statistics are built.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReproductionOutcome {
+    pub eligible: u64,
+    pub pairs: u64,
+    pub births: u64,
+    /// Males that fathered at least one child; only counted with sexes.
+    pub sires: u64,
+    /// Pairs whose pooled resources did not cover the child's cost.
+    pub failed_attempts: u64,
+    /// Sum of pooled parental resources over all pairs.
+    pub parental_resources: f64,
+    /// Sum of child costs (2 * brain volume) over all pairs.
+    pub child_cost: f64,
+    /// Sum of resources handed to the newborns.
+    pub child_endowment: f64,
+    /// Resources actually consumed by successful births.
+    pub resources_spent: f64,
+}
+
+impl ReproductionOutcome {
+    pub fn merge(&mut self, other: &ReproductionOutcome) {
+        self.eligible += other.eligible;
+        self.pairs += other.pairs;
+        self.births += other.births;
+        self.sires += other.sires;
+        self.failed_attempts += other.failed_attempts;
+        self.parental_resources += other.parental_resources;
+        self.child_cost += other.child_cost;
+        self.child_endowment += other.child_endowment;
+        self.resources_spent += other.resources_spent;
+    }
+}
 
 pub fn attempt_reproduction<R: Rng + ?Sized>(
     parent_a: &mut Agent,
     parent_b: &mut Agent,
     mut_cfg: MutationCfg,
     rng: &mut R,
+    outcome: &mut ReproductionOutcome,
 ) -> Option<Agent> {
     // Step 1: Child inherits one allele from each parent for each trait.
     // memory_capacity:
@@ -73,12 +115,7 @@ pub fn attempt_reproduction<R: Rng + ?Sized>(
     };
     // clamp within [0.0, 1.0]
     for val in &mut [&mut child_le_alleles.allele1, &mut child_le_alleles.allele2] {
-        if **val < 0.0 {
-            **val = 0.0;
-        }
-        if **val > 1.0 {
-            **val = 1.0;
-        }
+        **val = val.clamp(0.0, 1.0);
     }
 
     let mut child_te_alleles = Alleles {
@@ -87,12 +124,7 @@ pub fn attempt_reproduction<R: Rng + ?Sized>(
     };
     // clamp within [0.0, 1.0]
     for val in &mut [&mut child_te_alleles.allele1, &mut child_te_alleles.allele2] {
-        if **val < 0.0 {
-            **val = 0.0;
-        }
-        if **val > 1.0 {
-            **val = 1.0;
-        }
+        **val = val.clamp(0.0, 1.0);
     }
 
     // Step 3: Build a "potential child" to compute brain volume
@@ -101,16 +133,22 @@ pub fn attempt_reproduction<R: Rng + ?Sized>(
         child_mc_alleles,
         child_le_alleles,
         child_te_alleles,
-        parent_a.config.clone(),
+        parent_a.config,
     );
 
-    // Step 4: Reproduction cost = 2 * child's brain volume
+    // Step 4: Reproduction cost = 2 * child's brain volume, reduced by the
+    // parents' fertility memes
     let child_brain_volume = potential_child.get_brain_volume();
-    let child_cost = 2.0 * child_brain_volume;
+    let fertility = 0.5 * (parent_a.fertility_modifier + parent_b.fertility_modifier);
+    let child_cost = 2.0 * child_brain_volume / (1.0 + fertility.max(0.0));
 
     // Check resources from both parents
     let mut resource_pool = parent_a.resources + parent_b.resources;
+    outcome.pairs += 1;
+    outcome.parental_resources += resource_pool;
+    outcome.child_cost += child_cost;
     if resource_pool < child_cost {
+        outcome.failed_attempts += 1;
         return None; // not enough
     }
 
@@ -126,39 +164,200 @@ pub fn attempt_reproduction<R: Rng + ?Sized>(
 
     let mut child = potential_child;
     child.resources = child_share;
+    child.parents = Some((parent_a.id, parent_b.id));
+
+    outcome.births += 1;
+    outcome.child_endowment += child_share;
+    outcome.resources_spent += child_cost;
 
     Some(child)
 }
 
-pub fn reproduce_group<R: Rng + ?Sized>(group: &mut Group, rng: &mut R, mut_cfg: MutationCfg) {
-    let mut eligible_indices: Vec<usize> = group
+/// Weight of `male` as a mate of `female` under the given mate choice.
+fn mate_weight(female: &Agent, male: &Agent, cfg: &MatingCfg) -> f64 {
+    match cfg.choice {
+        MateChoice::Random => 1.0,
+        MateChoice::Trick => (1.0 + male.trick_efficiency).max(0.0).powf(cfg.strength),
+        MateChoice::Resources => (1.0 + male.resources).max(0.0).powf(cfg.strength),
+        MateChoice::SimilarBrain => {
+            let difference = (female.get_brain_volume() - male.get_brain_volume()).abs();
+            (1.0 + difference).powf(-cfg.strength)
+        }
+    }
+}
+
+/// Pairs eligible agents uniformly at random.
+fn random_pairs<R: Rng + ?Sized>(mut eligible: Vec<usize>, rng: &mut R) -> Vec<(usize, usize)> {
+    eligible.shuffle(rng);
+    eligible
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Every eligible female, in random order, chooses a male among those who
+/// have not reached `max_mates` yet. Returns `(female, male)` pairs.
+fn chosen_pairs<R: Rng + ?Sized>(
+    members: &[Agent],
+    eligible: &[usize],
+    cfg: &MatingCfg,
+    rng: &mut R,
+) -> Vec<(usize, usize)> {
+    let (mut females, males): (Vec<usize>, Vec<usize>) = eligible
+        .iter()
+        .partition(|&&i| members[i].sex == Sex::Female);
+    females.shuffle(rng);
+    let mut mates = vec![0; males.len()];
+    let mut pairs = Vec::new();
+    for female in females {
+        let weights: Vec<f64> = males
+            .iter()
+            .zip(&mates)
+            .map(|(&male, &count)| {
+                if count < cfg.max_mates {
+                    mate_weight(&members[female], &members[male], cfg)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let chosen = match WeightedIndex::new(&weights) {
+            Ok(dist) => dist.sample(rng),
+            Err(_) => break,
+        };
+        mates[chosen] += 1;
+        pairs.push((female, males[chosen]));
+    }
+    pairs
+}
+
+pub fn reproduce_group<R: Rng + ?Sized>(
+    group: &mut Group,
+    rng: &mut R,
+    mut_cfg: MutationCfg,
+    mating_cfg: &MatingCfg,
+) -> ReproductionOutcome {
+    let mut outcome = ReproductionOutcome::default();
+    let eligible_indices: Vec<usize> = group
         .members
         .iter()
         .enumerate()
         .filter(|(_, agent)| agent.age >= 6)
         .map(|(i, _)| i)
         .collect();
+    outcome.eligible = eligible_indices.len() as u64;
 
-    eligible_indices.shuffle(rng);
+    let pairs = if mating_cfg.sexes {
+        chosen_pairs(&group.members, &eligible_indices, mating_cfg, rng)
+    } else {
+        random_pairs(eligible_indices, rng)
+    };
 
     let mut children = Vec::new();
+    let mut sires = HashSet::new();
 
-    for pair in eligible_indices.chunks(2) {
-        if pair.len() == 2 {
-            let i1 = pair[0];
-            let i2 = pair[1];
+    for (i1, i2) in pairs {
+        let (parent_a, parent_b) = get_two_mut(&mut group.members, i1, i2);
+        if let Some(mut child) =
+            attempt_reproduction(parent_a, parent_b, mut_cfg, rng, &mut outcome)
+        {
+            if mating_cfg.sexes {
+                sires.insert(i2);
+                child.sex = Sex::random(rng);
+            }
+            children.push(child);
+        }
+    }
+    outcome.sires = sires.len() as u64;
 
-            let (i1, i2) = if i1 < i2 { (i1, i2) } else { (i2, i1) };
+    group.members.extend(children);
 
-            let (left, right) = group.members.split_at_mut(i2);
-            let parent_a = &mut left[i1];
-            let parent_b = &mut right[0];
+    outcome
+}
 
-            if let Some(child) = attempt_reproduction(parent_a, parent_b, mut_cfg, rng) {
-                children.push(child);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    /// Adults of the given sexes; the first male is by far the richest.
+    fn adults<R: Rng + ?Sized>(females: usize, males: usize, rng: &mut R) -> Vec<Agent> {
+        let mut members: Vec<Agent> = (0..females + males)
+            .map(|_| Agent::default(rng, AgentCfg::default()))
+            .collect();
+        for (i, agent) in members.iter_mut().enumerate() {
+            agent.age = 20;
+            agent.resources = 100.0;
+            agent.sex = if i < females { Sex::Female } else { Sex::Male };
         }
+        members[females].resources = 1000.0;
+        members
     }
 
-    group.members.extend(children);
+    fn mates_per_male(pairs: &[(usize, usize)], members: &[Agent]) -> Vec<usize> {
+        let mut counts: Vec<usize> = (0..members.len())
+            .filter(|&i| members[i].sex == Sex::Male)
+            .map(|male| pairs.iter().filter(|(_, m)| *m == male).count())
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    #[test]
+    fn males_mate_at_most_max_mates_times() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(17);
+        let members = adults(6, 2, &mut rng);
+        let eligible: Vec<usize> = (0..members.len()).collect();
+        let mut cfg = MatingCfg {
+            sexes: true,
+            choice: MateChoice::Random,
+            strength: 1.0,
+            max_mates: 2,
+        };
+        let pairs = chosen_pairs(&members, &eligible, &cfg, &mut rng);
+        assert_eq!(mates_per_male(&pairs, &members), vec![2, 2]);
+        assert!(pairs
+            .iter()
+            .all(|&(f, m)| members[f].sex == Sex::Female && members[m].sex == Sex::Male));
+
+        // A strong preference for resources gives the rich male all the
+        // females he may take
+        cfg.choice = MateChoice::Resources;
+        cfg.strength = 5.0;
+        cfg.max_mates = 4;
+        let pairs = chosen_pairs(&members, &eligible, &cfg, &mut rng);
+        assert_eq!(mates_per_male(&pairs, &members), vec![2, 4]);
+        assert!(pairs.iter().take(4).all(|&(_, m)| m == 6));
+    }
+
+    #[test]
+    fn polygynous_males_sire_several_children() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(18);
+        let mut group = Group::new(
+            0,
+            AgentCfg::default(),
+            GroupCfg {
+                max_size: 100,
+                fission: FissionPolicy::default(),
+                min_size: 0,
+            },
+            &mut rng,
+        );
+        group.members = adults(4, 1, &mut rng);
+        let cfg = MatingCfg {
+            sexes: true,
+            choice: MateChoice::Random,
+            strength: 1.0,
+            max_mates: 3,
+        };
+        let mutation = crate::config::file::example_config().mutation_config;
+        let outcome = reproduce_group(&mut group, &mut rng, mutation, &cfg);
+        assert_eq!((outcome.eligible, outcome.pairs), (5, 3));
+        assert_eq!((outcome.births, outcome.sires), (3, 1));
+        let children = &group.members[5..];
+        assert_eq!(children.len(), 3);
+        assert!(children.iter().all(|child| child.sex != Sex::Unspecified));
+    }
 }
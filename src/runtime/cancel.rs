@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::process;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::{signal, time};
+
+/// Runs and ensembles in progress in this process, with whether their
+/// cancellation was requested.
+fn running() -> &'static Mutex<HashMap<String, bool>> {
+    static RUNNING: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Makes a run or ensemble that is starting cancellable.
+pub fn register_running(uuid: &str) {
+    running().lock().unwrap().insert(uuid.to_string(), false);
+}
+
+/// Forgets a finished run or ensemble, along with its cancellation request.
+pub fn unregister_running(uuid: &str) {
+    running().lock().unwrap().remove(uuid);
+}
+
+/// Asks a run, or all replicates of an ensemble, running in this process
+/// to stop after the current year. Returns `false` if no such run or
+/// ensemble is in progress.
+pub fn request_cancel(uuid: &str) -> bool {
+    match running().lock().unwrap().get_mut(uuid) {
+        Some(requested) => {
+            *requested = true;
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn cancel_requested(uuid: &str) -> bool {
+    running()
+        .lock()
+        .unwrap()
+        .get(uuid)
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Asks every run and ensemble in progress in this process to stop after
+/// the current year.
+pub fn request_cancel_all() {
+    for requested in running().lock().unwrap().values_mut() {
+        *requested = true;
+    }
+}
+
+/// Waits until every run and ensemble of this process has stopped and
+/// stored its state.
+pub async fn wait_for_runs() {
+    while !running().lock().unwrap().is_empty() {
+        time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Cancels all runs on the first Ctrl-C, so that they are stored as
+/// `cancelled` instead of staying `running`, and exits on the second one.
+pub async fn cancel_on_interrupt() {
+    if signal::ctrl_c().await.is_err() {
+        return;
+    }
+    println!("Interrupted, stopping after the current year; press Ctrl-C again to quit now");
+    request_cancel_all();
+    if signal::ctrl_c().await.is_ok() {
+        process::exit(130);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_running_uuids_can_be_cancelled() {
+        assert!(!request_cancel("unknown"));
+        assert!(!cancel_requested("unknown"));
+
+        register_running("run");
+        assert!(!cancel_requested("run"));
+        assert!(request_cancel("run"));
+        assert!(cancel_requested("run"));
+
+        unregister_running("run");
+        assert!(!cancel_requested("run"));
+        assert!(!request_cancel("run"));
+
+        register_running("first");
+        register_running("second");
+        request_cancel_all();
+        assert!(cancel_requested("first") && cancel_requested("second"));
+        unregister_running("first");
+        unregister_running("second");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::config::*;
+use crate::db::backend::*;
+use crate::runtime::cancel::*;
+use crate::runtime::run_sim::*;
+use crate::runtime::statistics::*;
+use crate::runtime::stop::*;
+use crate::utils::*;
+
+use rand::Rng;
+use rand_xoshiro::rand_core::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Identifies a run as one replicate of an ensemble.
+#[derive(Debug, Clone)]
+pub struct ReplicateTag {
+    pub ensemble_uuid: String,
+    pub index: usize,
+    /// Summary of the yearly global statistics the replicates add to once
+    /// they finish.
+    pub summary: Arc<Mutex<EnsembleSummary>>,
+}
+
+/// Seeds of the replicates, drawn from a generator seeded with `base_seed`.
+pub fn derive_seeds(base_seed: u64, replicates: usize) -> Vec<u64> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(base_seed);
+    (0..replicates).map(|_| rng.gen()).collect()
+}
+
+pub async fn initiate_ensemble(
+    ensemble_uuid: String,
+    cfg: SimConfig,
+    replicates: usize,
+) -> Option<usize> {
+    run_ensemble(ensemble_uuid, cfg, replicates, &Backend::from_env()).await
+}
+
+/// Runs `replicates` copies of `cfg`, each with its own seed and run id, and
+/// stores the yearly summaries of the ensemble. At most one replicate per
+/// available CPU runs at a time, each on a thread of the blocking pool.
+/// Returns the number of replicates that failed, or `None` if the ensemble
+/// could not be run.
+pub async fn run_ensemble(
+    ensemble_uuid: String,
+    cfg: SimConfig,
+    replicates: usize,
+    backend: &Backend,
+) -> Option<usize> {
+    if replicates == 0 {
+        println!("Ensemble {} needs at least one replicate", ensemble_uuid);
+        return None;
+    }
+    let base_seed = cfg
+        .seed
+        .unwrap_or_else(|| Xoshiro256PlusPlus::from_entropy().gen());
+    match backend
+        .store_ensemble(&ensemble_uuid, replicates, base_seed, &cfg)
+        .await
+    {
+        Ok(_) => {
+            println!("Successfully stored ensemble {}", ensemble_uuid);
+        }
+        Err(e) => {
+            println!("Failed to store ensemble {}: {}", ensemble_uuid, e);
+        }
+    }
+
+    register_running(&ensemble_uuid);
+    let summary = Arc::new(Mutex::new(EnsembleSummary::new(replicates)));
+    let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+    let permits = Arc::new(Semaphore::new(parallelism));
+    let handles: Vec<_> = derive_seeds(base_seed, replicates)
+        .into_iter()
+        .enumerate()
+        .map(|(index, seed)| {
+            let mut replicate_cfg = cfg.clone();
+            replicate_cfg.seed = Some(seed);
+            let tag = ReplicateTag {
+                ensemble_uuid: ensemble_uuid.clone(),
+                index,
+                summary: summary.clone(),
+            };
+            let backend = backend.clone();
+            let permits = permits.clone();
+            task::spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                spawn_run(generate_uuid(), replicate_cfg, backend, Some(tag)).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::new();
+    let mut failed = 0;
+    for handle in handles {
+        match handle.await {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => failed += 1,
+            Err(e) => {
+                println!("Replicate of ensemble {} failed: {}", ensemble_uuid, e);
+                failed += 1;
+            }
+        }
+    }
+
+    unregister_running(&ensemble_uuid);
+
+    let extinct = results
+        .iter()
+        .filter(|r| r.stop_reason == StopReason::Extinction)
+        .count();
+    let extinct_fraction = extinct as f64 / results.len().max(1) as f64;
+    println!(
+        "Ensemble {}: {} of {} replicates finished, {} went extinct, {} failed",
+        ensemble_uuid,
+        results.len(),
+        replicates,
+        extinct,
+        failed
+    );
+
+    let rows = build_ensemble_statistics(ensemble_uuid.clone(), &summary.lock().unwrap(), &results);
+    if let Err(e) = backend.insert_ensemble_stats(&rows).await {
+        println!("Error while inserting into Clickhouse: {:?}", e);
+    }
+    if let Err(e) = backend
+        .store_ensemble_result(&ensemble_uuid, extinct_fraction, failed)
+        .await
+    {
+        println!(
+            "Failed to store the result of ensemble {}: {}",
+            ensemble_uuid, e
+        );
+    }
+    Some(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_depend_only_on_the_base_seed() {
+        let seeds = derive_seeds(42, 8);
+        assert_eq!(seeds.len(), 8);
+        assert_eq!(seeds, derive_seeds(42, 8));
+        assert_eq!(&derive_seeds(42, 3)[..], &seeds[..3]);
+        assert_ne!(seeds, derive_seeds(43, 8));
+        for (i, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[..i].contains(seed), "seed {} repeats", seed);
+        }
+        assert!(derive_seeds(42, 0).is_empty());
+    }
+}
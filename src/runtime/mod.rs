@@ -1,2 +1,5 @@
+pub mod cancel;
+pub mod ensemble;
 pub mod run_sim;
 pub mod statistics;
+pub mod stop;
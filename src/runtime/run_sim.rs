@@ -1,121 +1,286 @@
-use crate::simulation::group::*;
+use crate::simulation::environment::*;
+use crate::simulation::landscape::*;
 
+use crate::model::conflict::*;
 use crate::model::culture::*;
 use crate::model::distribution::*;
 use crate::model::population::*;
 use crate::model::reproduction::*;
 
-use crate::db::clickhouse_client::*;
-use crate::db::mysql_client::*;
+use crate::db::backend::*;
+use crate::db::parquet_writer::*;
 
 use crate::config::config::*;
+use crate::config::file::*;
+use crate::utils::*;
 
+use crate::runtime::cancel::*;
+use crate::runtime::ensemble::*;
 use crate::runtime::statistics::*;
+use crate::runtime::stop::*;
 
-use mysql_async::Pool;
-
+use rand::Rng;
 use rand_xoshiro::rand_core::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus;
+use tokio::runtime::Handle;
+use tokio::task;
+
+impl From<StopReason> for RunState {
+    fn from(reason: StopReason) -> Self {
+        match reason {
+            StopReason::Extinction => RunState::Extinct,
+            StopReason::Cancelled => RunState::Cancelled,
+            _ => RunState::Completed,
+        }
+    }
+}
 
-use std::env;
-
-pub async fn initiate_run(sim_uuid: String, cfg: SimConfig) {
-    let clickhouse_url = env::var("CLICKHOUSE_URL").expect("CLICKHOUSE_URL must be set");
-    let user = env::var("CLICKHOUSE_USER").expect("CLICKHOUSE_USER must be set");
-    let password = env::var("CLICKHOUSE_PASSWORD").expect("CLICKHOUSE_PASSWORD must be set");
-    let database = env::var("CLICKHOUSE_DB").expect("CLICKHOUSE_DB must be set");
-    let mysql_url = env::var("MYSQL_URL").expect("MYSQL_URL must be set");
-    let pool = mysql_async::Pool::new(mysql_url.as_str());
-    multi_group_run(
-        sim_uuid,
-        cfg,
-        clickhouse_url.as_str(),
-        &pool,
-        &DBCreds {
-            user: user,
-            password: password,
-            database: database,
-        },
-    )
-    .await;
+/// Summary of a finished run.
+#[derive(Debug)]
+pub struct RunResult {
+    pub stop_reason: StopReason,
+    pub last_year: usize,
+}
+
+pub async fn initiate_run(sim_uuid: String, cfg: SimConfig) -> Option<RunResult> {
+    spawn_run(sim_uuid, cfg, Backend::from_env(), None).await
+}
+
+/// Runs `multi_group_run` on a thread of the blocking pool, so that the
+/// simulation never occupies a worker of the async runtime. A run that
+/// panics is stored as failed.
+pub async fn spawn_run(
+    sim_uuid: String,
+    cfg: SimConfig,
+    backend: Backend,
+    replicate: Option<ReplicateTag>,
+) -> Option<RunResult> {
+    let runtime = Handle::current();
+    let (uuid, run_backend) = (sim_uuid.clone(), backend.clone());
+    let run = task::spawn_blocking(move || {
+        runtime.block_on(multi_group_run(uuid, cfg, &run_backend, replicate.as_ref()))
+    });
+    match run.await {
+        Ok(result) => result,
+        Err(e) => {
+            println!("Run {} failed: {}", sim_uuid, e);
+            unregister_running(&sim_uuid);
+            if let Err(e) = backend
+                .store_run_state(sim_uuid.as_str(), RunState::Failed)
+                .await
+            {
+                println!("Failed to store the state of run {}: {}", sim_uuid, e);
+            }
+            None
+        }
+    }
 }
 
+/// Runs one simulation. Returns `None` if the run could not be started.
 pub async fn multi_group_run(
     sim_uuid: String,
     cfg: SimConfig,
-    clickhouse_url: &str,
-    mysql_pool: &Pool,
-    creds: &DBCreds,
-) {
-    let mut rng = Xoshiro256PlusPlus::from_entropy();
-    let resources: f64 = cfg.resources;
+    backend: &Backend,
+    replicate: Option<&ReplicateTag>,
+) -> Option<RunResult> {
+    let seed = cfg
+        .seed
+        .unwrap_or_else(|| Xoshiro256PlusPlus::from_entropy().gen());
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut environment = Environment::new(cfg.resources, cfg.environment_config.clone());
     let epoch = cfg.epoch;
 
-    let init_members: usize = (cfg.resources as usize) / 17 / 3;
-    let mut groups: Vec<Group> = vec![
-        Group::new(init_members, cfg.agent_config, cfg.group_config, &mut rng),
-        Group::new(init_members, cfg.agent_config, cfg.group_config, &mut rng),
-        Group::new(init_members, cfg.agent_config, cfg.group_config, &mut rng),
-    ];
-
-    match store_simulation_config(mysql_pool, sim_uuid.as_str(), &cfg).await {
+    match backend
+        .store_simulation_config(sim_uuid.as_str(), &cfg)
+        .await
+    {
         Ok(_) => {
             println!("Successfully stored configuration for run {}", sim_uuid);
         }
         Err(e) => {
-            println!(
-                "Failed to store configuration for run {} in mysql: {}",
-                sim_uuid, e
-            );
+            println!("Failed to store configuration for run {}: {}", sim_uuid, e);
         }
     }
+    if let Err(e) = backend
+        .store_run_start(sim_uuid.as_str(), seed, &code_version(), &host_name())
+        .await
+    {
+        println!("Failed to store the start of run {}: {}", sim_uuid, e);
+    }
 
-    let mut global_stats_batch: Vec<GlobalStatsRow> = Vec::new();
-    let mut meme_stats_batch: Vec<MemeStatsRow> = Vec::new();
+    if let Some(tag) = replicate {
+        if let Err(e) = backend
+            .store_replicate_tag(sim_uuid.as_str(), tag.ensemble_uuid.as_str(), tag.index)
+            .await
+        {
+            println!("Failed to tag run {} as a replicate: {}", sim_uuid, e);
+        }
+    }
 
+    let founders = founding_groups(&cfg, &mut rng).map_err(|e| e.to_string());
+    let stop_monitor = StopMonitor::new(&cfg.stop_config);
+    let (mut groups, mut stop_monitor) = match (founders, stop_monitor) {
+        (Ok(groups), Ok(monitor)) => (groups, monitor),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Failed to start run {}: {}", sim_uuid, e);
+            if let Err(e) = backend
+                .store_run_state(sim_uuid.as_str(), RunState::Failed)
+                .await
+            {
+                println!("Failed to store the state of run {}: {}", sim_uuid, e);
+            }
+            return None;
+        }
+    };
+
+    let landscape = cfg
+        .landscape_config
+        .map(|landscape_cfg| Landscape::new(landscape_cfg, &mut rng));
+    if let Some(landscape) = &landscape {
+        // Groups of a snapshot keep their patches if they fit the landscape
+        for group in groups
+            .iter_mut()
+            .filter(|g| g.patch.is_none_or(|p| p >= landscape.patch_count()))
+        {
+            group.patch = Some(if landscape.config.clustered_start {
+                landscape.centre()
+            } else {
+                landscape.random_patch(&mut rng)
+            });
+        }
+    }
+
+    let mut stats_batch = StatsBatch::default();
+    let partition = Partition {
+        ensemble_uuid: replicate.map(|tag| tag.ensemble_uuid.clone()),
+        run_uuid: sim_uuid.clone(),
+    };
+    let mut summary = None;
+    // Added to the ensemble summary only if the replicate finishes
+    let mut replicate_rows = Vec::new();
+    let mut stop_reason = StopReason::Epoch;
+    let mut last_year = 0;
+
+    register_running(&sim_uuid);
     for year in 0..epoch {
+        let environment_state = environment.advance(year, &mut rng);
+        let mut flow = ResourceFlow::default();
+        let mut culture_log = CultureLog::default();
         groups.iter_mut().for_each(|group| {
-            dinner_time(group);
-            inventions(group, &cfg.meme_config, &mut rng);
-            amnesia(group, &mut rng);
-            perform_cultural_transfer(group, &mut rng, TransferMode::Teaching);
+            dinner_time(group, &mut flow);
+            inventions(group, &cfg, &mut rng, &mut culture_log);
+            amnesia(group, &cfg, &mut rng, &mut culture_log);
+            perform_cultural_transfer(
+                group,
+                &mut rng,
+                TransferMode::Teaching,
+                &cfg,
+                &mut culture_log,
+            );
         });
 
-        share_resources_across_groups(&mut groups, resources);
+        share_resources_across_groups(
+            &mut groups,
+            environment_state.resources,
+            landscape.as_ref(),
+            &cfg.sharing_config,
+            &mut rng,
+            &mut flow,
+        );
 
         groups.iter_mut().for_each(|group| {
-            useless(group, &mut rng);
-            perform_cultural_transfer(group, &mut rng, TransferMode::Learning);
-            grim_reaper(group, &mut rng);
+            useless(group, &mut rng, &mut flow);
+            perform_cultural_transfer(
+                group,
+                &mut rng,
+                TransferMode::Learning,
+                &cfg,
+                &mut culture_log,
+            );
+            grim_reaper(group, &cfg.mortality_config, &mut rng, &mut flow);
         });
 
-        clean_up_groups(&mut groups);
+        let mut conflict = ConflictOutcome::default();
+        if let Some(conflict_cfg) = &cfg.conflict_config {
+            conflict = perform_conflicts(
+                &mut groups,
+                conflict_cfg,
+                landscape.as_ref(),
+                &mut rng,
+                &mut flow,
+            );
+        }
 
+        fuse_small_groups(&mut groups, &mut rng, landscape.as_ref());
+        clean_up_groups(&mut groups, &mut flow);
+
+        let mut reproduction = ReproductionOutcome::default();
         groups.iter_mut().for_each(|group| {
-            reproduce_group(group, &mut rng, cfg.mutation_config);
+            reproduction.merge(&reproduce_group(
+                group,
+                &mut rng,
+                cfg.mutation_config,
+                &cfg.mating_config,
+            ));
+            vertical_transmission(group, &cfg, &mut rng, &mut culture_log);
         });
 
-        handle_group_splitting(&mut groups, &mut rng);
-        perform_migration(&mut groups, &mut rng, 0.001);
+        handle_group_splitting(&mut groups, &mut rng, landscape.as_ref());
+        let migration = perform_migration(
+            &mut groups,
+            &mut rng,
+            &cfg.migration_config,
+            landscape.as_ref(),
+            &mut culture_log,
+            &mut flow,
+        );
 
-        global_stats_batch.push(build_general_statistics(sim_uuid.clone(), year, &groups));
-        meme_stats_batch.extend(build_meme_statistics(sim_uuid.clone(), year, &groups));
+        let global = build_general_statistics(sim_uuid.clone(), year, &groups, &environment_state);
+        let stop = stop_monitor.check(&global);
+        if replicate.is_some() {
+            replicate_rows.push(global.clone());
+        }
+        summary = Some(global.clone());
+        stats_batch.global.push(global);
+        stats_batch.meme.extend(build_meme_statistics(
+            sim_uuid.clone(),
+            year,
+            &groups,
+            &cfg.meme_kinds,
+            &culture_log,
+        ));
+        stats_batch.reproduction.push(build_reproduction_statistics(
+            sim_uuid.clone(),
+            year,
+            &reproduction,
+        ));
+        stats_batch.resources.push(build_resource_statistics(
+            sim_uuid.clone(),
+            year,
+            &flow,
+            &reproduction,
+            &groups,
+        ));
+        stats_batch
+            .group
+            .extend(build_group_statistics(sim_uuid.clone(), year, &flow));
+        stats_batch
+            .conflict
+            .push(build_conflict_statistics(sim_uuid.clone(), year, &conflict));
+        stats_batch.migration.push(build_migration_statistics(
+            sim_uuid.clone(),
+            year,
+            &migration,
+        ));
 
         if (year + 1) % 1000 == 0 {
-            match insert_global_stats(clickhouse_url, creds, &global_stats_batch).await {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("Error while inserting into Clickhouse: {:?}", e);
-                }
+            if let Some(root) = &cfg.parquet_dir {
+                stats_batch.write_parquet(root, &partition);
             }
-            global_stats_batch = Vec::new();
-            match insert_meme_stats(clickhouse_url, creds, &meme_stats_batch).await {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("Error while inserting into Clickhouse: {:?}", e);
-                }
+            stats_batch.flush(backend).await;
+            if let Err(e) = backend.store_run_progress(sim_uuid.as_str(), year).await {
+                println!("Failed to store the progress of run {}: {}", sim_uuid, e);
             }
-            meme_stats_batch = Vec::new();
         }
 
         groups.iter_mut().for_each(|group| {
@@ -126,22 +291,57 @@ pub async fn multi_group_run(
             print_group_statistics(&groups);
             println!("==================================================");
         }
-        if groups.len() == 0 {
+        last_year = year;
+        if groups.is_empty() {
             println!("Extinction at year {}!", year);
+            stop_reason = StopReason::Extinction;
             break;
         }
-    }
-    match insert_global_stats(clickhouse_url, creds, &global_stats_batch).await {
-        Ok(_) => {}
-        Err(e) => {
-            println!("Error while inserting into Clickhouse: {:?}", e);
+        if let Some(reason) = stop {
+            println!("Stopping at year {}: {}", year, reason);
+            stop_reason = reason;
+            break;
+        }
+        let cancelled = cancel_requested(&sim_uuid)
+            || replicate.is_some_and(|tag| cancel_requested(&tag.ensemble_uuid));
+        if cancelled {
+            println!("Run {} cancelled at year {}", sim_uuid, year);
+            stop_reason = StopReason::Cancelled;
+            break;
         }
     }
-    match insert_meme_stats(clickhouse_url, creds, &meme_stats_batch).await {
-        Ok(_) => {}
-        Err(e) => {
-            println!("Error while inserting into Clickhouse: {:?}", e);
+    unregister_running(&sim_uuid);
+    if let Some(tag) = replicate {
+        let mut ensemble_summary = tag.summary.lock().unwrap();
+        for row in &replicate_rows {
+            ensemble_summary.add(row);
         }
     }
+    if let Some(root) = &cfg.parquet_dir {
+        stats_batch.write_parquet(root, &partition);
+    }
+    stats_batch.flush(backend).await;
+    let summary = serde_json::to_string(&summary).unwrap_or_default();
+    if let Err(e) = backend
+        .store_run_result(
+            sim_uuid.as_str(),
+            RunState::from(stop_reason),
+            &stop_reason.to_string(),
+            last_year,
+            &summary,
+        )
+        .await
+    {
+        println!("Failed to store the result of run {}: {}", sim_uuid, e);
+    }
     print_group_statistics(&groups);
+    if let Some(path) = &cfg.final_snapshot {
+        if let Err(e) = save_snapshot(path, &groups) {
+            println!("Failed to save the snapshot of run {}: {}", sim_uuid, e);
+        }
+    }
+    Some(RunResult {
+        stop_reason,
+        last_year,
+    })
 }
@@ -1,27 +1,120 @@
 use crate::config::config::*;
+use crate::config::expression::*;
+use crate::db::backend::*;
 use crate::db::clickhouse_client::*;
+use crate::db::parquet_writer::*;
+use crate::db::sqlite_client;
+use crate::model::conflict::*;
+use crate::model::culture::*;
+use crate::model::distribution::*;
+use crate::model::population::*;
+use crate::model::reproduction::*;
+use crate::runtime::run_sim::*;
+use crate::runtime::stop::*;
+use crate::simulation::environment::*;
 use crate::simulation::group::*;
 
+use std::collections::HashSet;
+use std::mem;
+
+/// Yearly statistics rows accumulated between two writes to the backend.
+#[derive(Debug, Default)]
+pub struct StatsBatch {
+    pub global: Vec<GlobalStatsRow>,
+    pub meme: Vec<MemeStatsRow>,
+    pub reproduction: Vec<ReproductionStatsRow>,
+    pub resources: Vec<ResourceStatsRow>,
+    pub group: Vec<GroupStatsRow>,
+    pub conflict: Vec<ConflictStatsRow>,
+    pub migration: Vec<MigrationStatsRow>,
+}
+
+impl StatsBatch {
+    /// Inserts all accumulated rows and empties the batch. Insertion errors
+    /// are reported but do not interrupt the simulation.
+    pub async fn flush(&mut self, backend: &Backend) {
+        match backend {
+            Backend::Remote {
+                clickhouse_url,
+                creds,
+                ..
+            } => self.insert_into_clickhouse(clickhouse_url, creds).await,
+            Backend::Sqlite { db, .. } => {
+                let global = mem::take(&mut self.global);
+                let meme = mem::take(&mut self.meme);
+                let inserted = db
+                    .run(move |conn| sqlite_client::insert_stats(conn, &global, &meme))
+                    .await;
+                if let Err(e) = inserted {
+                    println!("Error while inserting into SQLite: {:?}", e);
+                }
+            }
+        }
+        self.global.clear();
+        self.meme.clear();
+        self.reproduction.clear();
+        self.resources.clear();
+        self.group.clear();
+        self.conflict.clear();
+        self.migration.clear();
+    }
+
+    /// Writes the global, meme and group rows to Parquet files; call before
+    /// `flush`, which empties the batch.
+    pub fn write_parquet(&self, root: &str, partition: &Partition) {
+        if let Err(e) = write_stats(root, partition, &self.global, &self.meme, &self.group) {
+            println!("Error while writing Parquet files: {:?}", e);
+        }
+    }
+
+    async fn insert_into_clickhouse(&self, clickhouse_url: &str, creds: &DBCreds) {
+        if let Err(e) = insert_global_stats(clickhouse_url, creds, &self.global).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_meme_stats(clickhouse_url, creds, &self.meme).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_reproduction_stats(clickhouse_url, creds, &self.reproduction).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_resource_stats(clickhouse_url, creds, &self.resources).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_group_stats(clickhouse_url, creds, &self.group).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_conflict_stats(clickhouse_url, creds, &self.conflict).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+        if let Err(e) = insert_migration_stats(clickhouse_url, creds, &self.migration).await {
+            println!("Error while inserting into Clickhouse: {:?}", e);
+        }
+    }
+}
+
 pub fn build_general_statistics(
     simulation_id: String,
     year: usize,
-    groups: &Vec<Group>,
+    groups: &[Group],
+    environment: &EnvironmentState,
 ) -> GlobalStatsRow {
     let mut total_memes_known: u64 = 0;
     let mut headcount: u64 = 0;
-    let mut avg_memes_known: f64 = 0.0;
     let mut avg_trick_efficiency: f64 = 0.0;
     let mut avg_brain_volume: f64 = 0.0;
     let mut avg_meme_size: f64 = 0.0;
+    let mut max_meme_depth: u32 = 0;
+    let mut avg_meme_depth: f64 = 0.0;
     for group in groups {
         headcount += group.members.len() as u64;
         for member in &group.members {
-            avg_memes_known += member.memes.len() as f64;
             total_memes_known += member.memes.len() as u64;
             avg_trick_efficiency += member.trick_efficiency;
             avg_brain_volume += member.get_brain_volume();
             for meme in &member.memes {
                 avg_meme_size += meme.size;
+                avg_meme_depth += meme.depth as f64;
+                max_meme_depth = max_meme_depth.max(meme.depth);
             }
         }
     }
@@ -34,74 +127,357 @@ pub fn build_general_statistics(
             avg_trick_efficiency: 0.0,
             avg_brain_volume: 0.0,
             avg_meme_size: 0.0,
+            resources: environment.resources,
+            shock: environment.shock,
+            max_meme_depth: 0,
+            avg_meme_depth: 0.0,
+            headcount: 0,
+            group_count: groups.len() as u32,
         };
     }
-    avg_memes_known = (total_memes_known as f64) / (headcount as f64);
+    let avg_memes_known = (total_memes_known as f64) / (headcount as f64);
     avg_trick_efficiency /= headcount as f64;
     avg_brain_volume /= headcount as f64;
     if total_memes_known > 0 {
         avg_meme_size /= total_memes_known as f64;
+        avg_meme_depth /= total_memes_known as f64;
     }
 
     GlobalStatsRow {
         simulation_id: simulation_id.clone(),
         year: year as u32,
-        total_memes_known: total_memes_known,
-        avg_memes_known: avg_memes_known,
-        avg_trick_efficiency: avg_trick_efficiency,
-        avg_brain_volume: avg_brain_volume,
-        avg_meme_size: avg_meme_size,
+        total_memes_known,
+        avg_memes_known,
+        avg_trick_efficiency,
+        avg_brain_volume,
+        avg_meme_size,
+        resources: environment.resources,
+        shock: environment.shock,
+        max_meme_depth,
+        avg_meme_depth,
+        headcount,
+        group_count: groups.len() as u32,
     }
 }
 
 pub fn build_meme_statistics(
     simulation_id: String,
     year: usize,
-    groups: &Vec<Group>,
+    groups: &[Group],
+    meme_kinds: &[MemeKindCfg],
+    log: &CultureLog,
 ) -> Vec<MemeStatsRow> {
-    let meme_types: Vec<MemeType> = vec![
-        MemeType::Hunting,
-        MemeType::Learning,
-        MemeType::Teaching,
-        MemeType::Trick,
-        MemeType::Useless,
-    ];
     let mut res: Vec<MemeStatsRow> = Vec::new();
-    for meme_type in meme_types {
+    let headcount: usize = groups.iter().map(|group| group.members.len()).sum();
+    for (meme_type, meme_kind) in meme_kinds.iter().enumerate() {
         let mut tot_memes: u64 = 0;
         let mut avg_size: f64 = 0.0;
         let mut avg_eff: f64 = 0.0;
+        let mut carriers: u64 = 0;
+        let mut max_depth: u32 = 0;
+        let mut distinct: HashSet<usize> = HashSet::new();
         for group in groups {
-            if group.members.len() == 0 {
+            if group.members.is_empty() {
                 continue;
             }
             for member in &group.members {
+                let mut carrier = false;
                 for meme in &member.memes {
                     if meme.kind == meme_type {
                         tot_memes += 1;
                         avg_size += meme.size;
                         avg_eff += meme.effect;
+                        distinct.insert(meme.id);
+                        max_depth = max_depth.max(meme.depth);
+                        carrier = true;
                     }
                 }
+                if carrier {
+                    carriers += 1;
+                }
             }
         }
         if tot_memes > 0 {
             avg_size /= tot_memes as f64;
             avg_eff /= tot_memes as f64;
         }
+        let mut carrier_fraction: f64 = 0.0;
+        if headcount > 0 {
+            carrier_fraction = carriers as f64 / headcount as f64;
+        }
+        let events = log.get(meme_type);
         res.push(MemeStatsRow {
             simulation_id: simulation_id.clone(),
             year: year as u32,
-            meme_kind: format!("{:?}", meme_type),
+            meme_kind: meme_kind.name.clone(),
             avg_meme_efficiency: avg_eff,
             avg_meme_size: avg_size,
+            copies: tot_memes,
+            distinct_memes: distinct.len() as u64,
+            carrier_fraction,
+            inventions: events.inventions,
+            transfers: events.transfers,
+            vertical_transfers: events.vertical_transfers,
+            losses: events.losses,
+            copy_errors: events.copy_errors,
+            max_depth,
         });
     }
 
     res
 }
 
-pub fn print_group_statistics(groups: &Vec<Group>) {
+pub fn build_reproduction_statistics(
+    simulation_id: String,
+    year: usize,
+    outcome: &ReproductionOutcome,
+) -> ReproductionStatsRow {
+    let mut mean_parental_resources: f64 = 0.0;
+    let mut mean_child_cost: f64 = 0.0;
+    let mut mean_child_endowment: f64 = 0.0;
+    if outcome.pairs > 0 {
+        mean_parental_resources = outcome.parental_resources / outcome.pairs as f64;
+        mean_child_cost = outcome.child_cost / outcome.pairs as f64;
+    }
+    if outcome.births > 0 {
+        mean_child_endowment = outcome.child_endowment / outcome.births as f64;
+    }
+
+    ReproductionStatsRow {
+        simulation_id,
+        year: year as u32,
+        eligible_agents: outcome.eligible,
+        pairs: outcome.pairs,
+        births: outcome.births,
+        sires: outcome.sires,
+        failed_attempts: outcome.failed_attempts,
+        mean_parental_resources,
+        mean_child_cost,
+        mean_child_endowment,
+    }
+}
+
+pub fn build_resource_statistics(
+    simulation_id: String,
+    year: usize,
+    flow: &ResourceFlow,
+    reproduction: &ReproductionOutcome,
+    groups: &[Group],
+) -> ResourceStatsRow {
+    let total_held: f64 = groups
+        .iter()
+        .flat_map(|group| group.members.iter().map(|agent| agent.resources))
+        .sum();
+
+    ResourceStatsRow {
+        simulation_id,
+        year: year as u32,
+        total_distributed: flow.distributed,
+        gini: flow.gini,
+        spent_hunting: flow.hunting,
+        spent_food: flow.food,
+        spent_useless: flow.useless,
+        spent_reproduction: reproduction.resources_spent,
+        lost_with_dead: flow.lost_with_dead,
+        lost_with_dissolved: flow.lost_with_dissolved,
+        lost_in_migration: flow.lost_in_migration,
+        expired: flow.expired,
+        total_held,
+    }
+}
+
+pub fn build_group_statistics(
+    simulation_id: String,
+    year: usize,
+    flow: &ResourceFlow,
+) -> Vec<GroupStatsRow> {
+    flow.group_shares
+        .iter()
+        .map(|share| GroupStatsRow {
+            simulation_id: simulation_id.clone(),
+            year: year as u32,
+            group_id: share.group_id as u64,
+            patch: share.patch.map(|p| p as u32),
+            headcount: share.members as u64,
+            resource_share: share.share,
+        })
+        .collect()
+}
+
+pub fn build_conflict_statistics(
+    simulation_id: String,
+    year: usize,
+    outcome: &ConflictOutcome,
+) -> ConflictStatsRow {
+    ConflictStatsRow {
+        simulation_id,
+        year: year as u32,
+        conflicts: outcome.conflicts,
+        attacker_wins: outcome.attacker_wins,
+        casualties: outcome.casualties,
+        resources_looted: outcome.resources_looted,
+    }
+}
+
+pub fn build_migration_statistics(
+    simulation_id: String,
+    year: usize,
+    outcome: &MigrationOutcome,
+) -> MigrationStatsRow {
+    let mut mean_migrant_age: f64 = 0.0;
+    if outcome.migrants > 0 {
+        mean_migrant_age = outcome.migrant_age as f64 / outcome.migrants as f64;
+    }
+
+    MigrationStatsRow {
+        simulation_id,
+        year: year as u32,
+        migrants: outcome.migrants,
+        mean_migrant_age,
+        memes_carried: outcome.memes_carried,
+        memes_lost: outcome.memes_lost,
+        resources_lost: outcome.resources_lost,
+    }
+}
+
+/// Running summary of one statistic in one year over the replicates of an
+/// ensemble. Besides the mean and variance, only the values at both ends
+/// of the sample are kept, which is all the 2.5% and 97.5% quantiles need.
+#[derive(Debug, Clone, Default)]
+struct Sample {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    /// Smallest values, ascending.
+    lowest: Vec<f64>,
+    /// Largest values, ascending.
+    highest: Vec<f64>,
+}
+
+impl Sample {
+    fn add(&mut self, value: f64, kept: usize) {
+        // Welford's online mean and variance
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+
+        let at = self.lowest.partition_point(|v| v.total_cmp(&value).is_lt());
+        if at < kept {
+            self.lowest.insert(at, value);
+            self.lowest.truncate(kept);
+        }
+        let at = self
+            .highest
+            .partition_point(|v| v.total_cmp(&value).is_le());
+        if self.highest.len() < kept {
+            self.highest.insert(at, value);
+        } else if at > 0 {
+            self.highest.insert(at, value);
+            self.highest.remove(0);
+        }
+    }
+
+    fn sd(&self) -> f64 {
+        if self.count > 1 {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        } else {
+            0.0
+        }
+    }
+
+    /// Value at `index` of the sorted sample, which has to lie among the
+    /// kept values at either end.
+    fn sorted(&self, index: usize) -> f64 {
+        if index < self.lowest.len() {
+            self.lowest[index]
+        } else {
+            self.highest[self.highest.len() - (self.count - index)]
+        }
+    }
+
+    /// Linearly interpolated `p`-quantile.
+    fn quantile(&self, p: f64) -> f64 {
+        let position = p * (self.count - 1) as f64;
+        let (below, above) = (position.floor() as usize, position.ceil() as usize);
+        let (low, high) = (self.sorted(below), self.sorted(above));
+        low + (high - low) * (position - below as f64)
+    }
+}
+
+/// Global statistics summarized over the replicates of an ensemble.
+fn summarized_statistics() -> impl Iterator<Item = &'static str> {
+    VARIABLES.into_iter().filter(|c| *c != "year")
+}
+
+/// Yearly global statistics of the replicates of an ensemble, summarized as
+/// the replicates report them so that their histories need not be kept.
+#[derive(Debug)]
+pub struct EnsembleSummary {
+    /// Number of values kept at each end of a sample.
+    kept: usize,
+    /// One sample per summarized statistic for every year.
+    years: Vec<Vec<Sample>>,
+}
+
+impl EnsembleSummary {
+    pub fn new(replicates: usize) -> Self {
+        Self {
+            kept: (0.025 * replicates as f64).ceil() as usize + 2,
+            years: Vec::new(),
+        }
+    }
+
+    /// Adds the global statistics of one year of one replicate.
+    pub fn add(&mut self, row: &GlobalStatsRow) {
+        let year = row.year as usize;
+        if self.years.len() <= year {
+            let statistics = summarized_statistics().count();
+            self.years
+                .resize_with(year + 1, || vec![Sample::default(); statistics]);
+        }
+        for (sample, statistic) in self.years[year].iter_mut().zip(summarized_statistics()) {
+            sample.add(row.column(statistic), self.kept);
+        }
+    }
+}
+
+/// Summarizes every global statistic of every year over the replicates of
+/// an ensemble that finished. Replicates that stopped early leave the
+/// sample; extinct ones are counted in `extinct_fraction` of all later years.
+pub fn build_ensemble_statistics(
+    ensemble_id: String,
+    summary: &EnsembleSummary,
+    results: &[RunResult],
+) -> Vec<EnsembleStatsRow> {
+    let mut rows = Vec::new();
+    for (year, samples) in summary.years.iter().enumerate() {
+        let extinct = results
+            .iter()
+            .filter(|r| r.stop_reason == StopReason::Extinction && r.last_year <= year)
+            .count();
+        let extinct_fraction = extinct as f64 / results.len().max(1) as f64;
+        for (sample, statistic) in samples.iter().zip(summarized_statistics()) {
+            if sample.count == 0 {
+                continue;
+            }
+            rows.push(EnsembleStatsRow {
+                ensemble_id: ensemble_id.clone(),
+                year: year as u32,
+                statistic: statistic.to_string(),
+                replicates: sample.count as u32,
+                mean: sample.mean,
+                sd: sample.sd(),
+                lower: sample.quantile(0.025),
+                upper: sample.quantile(0.975),
+                extinct_fraction,
+            });
+        }
+    }
+    rows
+}
+
+pub fn print_group_statistics(groups: &[Group]) {
     for group in groups {
         println!(
             "Group {} ({} members) statistics",
@@ -141,3 +517,183 @@ pub fn print_group_statistics(groups: &Vec<Group>) {
         println!("Memes known: {}, avg size: {}, av_mc: {}, av_hu: {}, av_le: {}, av_te: {}, av_tre: {}, av_us: {}", total_memes_known, avg_meme_size, avg_mc, avg_hunting, avg_learning, avg_teaching, avg_trick, avg_useless);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::memetics::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn row(year: u32, headcount: u64) -> GlobalStatsRow {
+        GlobalStatsRow {
+            simulation_id: String::new(),
+            year,
+            total_memes_known: 0,
+            avg_memes_known: 0.0,
+            avg_trick_efficiency: 0.0,
+            avg_brain_volume: 0.0,
+            avg_meme_size: 0.0,
+            resources: 0.0,
+            shock: false,
+            max_meme_depth: 0,
+            avg_meme_depth: 0.0,
+            headcount,
+            group_count: 1,
+        }
+    }
+
+    fn result(stop_reason: StopReason, last_year: usize) -> RunResult {
+        RunResult {
+            stop_reason,
+            last_year,
+        }
+    }
+
+    fn headcounts(rows: &[EnsembleStatsRow]) -> Vec<&EnsembleStatsRow> {
+        rows.iter().filter(|r| r.statistic == "headcount").collect()
+    }
+
+    #[test]
+    fn summarizes_replicates_per_year() {
+        let mut summary = EnsembleSummary::new(4);
+        for headcount in [10, 20, 30, 40] {
+            summary.add(&row(0, headcount));
+        }
+        // one replicate went extinct in year 0, another one in year 1
+        for headcount in [12, 22, 32] {
+            summary.add(&row(1, headcount));
+        }
+        for headcount in [14, 24] {
+            summary.add(&row(2, headcount));
+        }
+        let results = [
+            result(StopReason::Epoch, 2),
+            result(StopReason::Epoch, 2),
+            result(StopReason::Extinction, 1),
+            result(StopReason::Extinction, 0),
+        ];
+        let rows = build_ensemble_statistics("e".to_string(), &summary, &results);
+        assert_eq!(rows.len(), 3 * summarized_statistics().count());
+        assert!(rows.iter().all(|r| r.statistic != "year"));
+
+        let expected = [(0, 4, 25.0, 0.25), (1, 3, 22.0, 0.5), (2, 2, 19.0, 0.5)];
+        for (row, (year, replicates, mean, extinct_fraction)) in
+            headcounts(&rows).into_iter().zip(expected)
+        {
+            assert_eq!(row.year, year);
+            assert_eq!(row.replicates, replicates);
+            assert!((row.mean - mean).abs() < 1e-12, "year {}", year);
+            assert_eq!(row.extinct_fraction, extinct_fraction);
+        }
+        let year0 = headcounts(&rows)[0];
+        assert!((year0.sd - (500.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((year0.lower - 10.75).abs() < 1e-12);
+        assert!((year0.upper - 39.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn quantiles_match_the_full_sample() {
+        let replicates = 200;
+        let mut summary = EnsembleSummary::new(replicates);
+        // a scrambled permutation of 0..200
+        let values: Vec<u64> = (0..replicates as u64).map(|i| i * 73 % 200).collect();
+        for &value in &values {
+            summary.add(&row(0, value));
+        }
+        let results: Vec<RunResult> = (0..replicates)
+            .map(|_| result(StopReason::Epoch, 0))
+            .collect();
+        let rows = build_ensemble_statistics("e".to_string(), &summary, &results);
+        let headcount = headcounts(&rows)[0];
+        assert_eq!(headcount.replicates, 200);
+        assert!((headcount.mean - 99.5).abs() < 1e-9);
+        assert!((headcount.lower - 4.975).abs() < 1e-9);
+        assert!((headcount.upper - 194.025).abs() < 1e-9);
+    }
+
+    #[test]
+    fn failed_replicates_do_not_count_as_survivors() {
+        let mut summary = EnsembleSummary::new(3);
+        summary.add(&row(0, 10));
+        summary.add(&row(0, 20));
+        // the third replicate failed and reported nothing
+        let results = [
+            result(StopReason::Epoch, 0),
+            result(StopReason::Extinction, 0),
+        ];
+        let rows = build_ensemble_statistics("e".to_string(), &summary, &results);
+        assert_eq!(headcounts(&rows)[0].replicates, 2);
+        assert_eq!(headcounts(&rows)[0].extinct_fraction, 0.5);
+    }
+
+    #[test]
+    fn meme_statistics_count_copies_carriers_and_events() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(3);
+        let group_cfg = GroupCfg {
+            max_size: 100,
+            fission: FissionPolicy::default(),
+            min_size: 0,
+        };
+        let mut groups = vec![
+            Group::new(3, AgentCfg::default(), group_cfg, &mut rng),
+            Group::new(1, AgentCfg::default(), group_cfg, &mut rng),
+        ];
+        let meme = |id: usize, kind: usize, size: f64, depth: u32| Meme {
+            id,
+            size,
+            kind,
+            target: EffectTarget::Hunting,
+            effect: 2.0 * size,
+            base_effect: 0.0,
+            complexity: 0.0,
+            prerequisite: None,
+            depth,
+        };
+        // Meme 1 is known twice, meme 2 once; the only trick meme is 3
+        groups[0].members[0].memes = vec![meme(1, 0, 1.0, 0), meme(2, 0, 2.0, 1)];
+        groups[0].members[1].memes = vec![meme(1, 0, 1.0, 0), meme(3, 1, 0.5, 0)];
+        let kinds = vec![
+            MemeKindCfg {
+                name: "Hunting".to_string(),
+                target: EffectTarget::Hunting,
+            },
+            MemeKindCfg {
+                name: "Trick".to_string(),
+                target: EffectTarget::Trick,
+            },
+            MemeKindCfg {
+                name: "Unused".to_string(),
+                target: EffectTarget::Useless,
+            },
+        ];
+        let mut log = CultureLog::default();
+        log.kind(0).inventions += 2;
+        log.kind(0).transfers += 1;
+        log.kind(1).vertical_transfers += 3;
+        log.kind(1).losses += 1;
+        log.kind(1).copy_errors += 1;
+
+        let rows = build_meme_statistics("s".to_string(), 7, &groups, &kinds, &log);
+        assert_eq!(rows.len(), 3);
+        let hunting = &rows[0];
+        assert_eq!((hunting.year, hunting.meme_kind.as_str()), (7, "Hunting"));
+        assert_eq!((hunting.copies, hunting.distinct_memes), (3, 2));
+        assert_eq!(hunting.carrier_fraction, 0.5);
+        assert!((hunting.avg_meme_size - 4.0 / 3.0).abs() < 1e-12);
+        assert!((hunting.avg_meme_efficiency - 8.0 / 3.0).abs() < 1e-12);
+        assert_eq!(hunting.max_depth, 1);
+        assert_eq!((hunting.inventions, hunting.transfers), (2, 1));
+        assert_eq!(hunting.vertical_transfers + hunting.losses, 0);
+
+        let trick = &rows[1];
+        assert_eq!((trick.copies, trick.distinct_memes), (1, 1));
+        assert_eq!(trick.carrier_fraction, 0.25);
+        assert_eq!((trick.vertical_transfers, trick.losses), (3, 1));
+        assert_eq!((trick.copy_errors, trick.inventions), (1, 0));
+
+        let unused = &rows[2];
+        assert_eq!((unused.copies, unused.carrier_fraction), (0, 0.0));
+        assert_eq!(unused.avg_meme_size, 0.0);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::config::config::*;
+use crate::config::expression::*;
+use crate::db::clickhouse_client::*;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+/// Why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// All `epoch` years were simulated.
+    Epoch,
+    Extinction,
+    MinHeadcount,
+    MaxHeadcount,
+    Plateau,
+    WallClock,
+    Expression,
+    /// Cancellation was requested while the run was in progress.
+    Cancelled,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StopReason::Epoch => "epoch",
+            StopReason::Extinction => "extinction",
+            StopReason::MinHeadcount => "min_headcount",
+            StopReason::MaxHeadcount => "max_headcount",
+            StopReason::Plateau => "plateau",
+            StopReason::WallClock => "wall_clock",
+            StopReason::Expression => "expression",
+            StopReason::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Least-squares slope of `values`, taken at consecutive years.
+fn slope(values: &VecDeque<f64>) -> f64 {
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, y) in values.iter().enumerate() {
+        let dx = x as f64 - mean_x;
+        covariance += dx * (y - mean_y);
+        variance += dx * dx;
+    }
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// Evaluates the configured stop conditions on the yearly global statistics.
+pub struct StopMonitor {
+    cfg: StopCfg,
+    started: Instant,
+    brain_volumes: VecDeque<f64>,
+    expression: Option<Expression>,
+}
+
+impl StopMonitor {
+    pub fn new(cfg: &StopCfg) -> Result<Self, String> {
+        let expression = cfg
+            .expression
+            .as_deref()
+            .map(Expression::parse)
+            .transpose()?;
+        Ok(Self {
+            cfg: cfg.clone(),
+            started: Instant::now(),
+            brain_volumes: VecDeque::new(),
+            expression,
+        })
+    }
+
+    /// Returns the reason to stop after the year summarized by `row`, if any.
+    /// Extinction is left to the simulation loop.
+    pub fn check(&mut self, row: &GlobalStatsRow) -> Option<StopReason> {
+        if self
+            .cfg
+            .min_headcount
+            .is_some_and(|min| row.headcount < min)
+        {
+            return Some(StopReason::MinHeadcount);
+        }
+        if self
+            .cfg
+            .max_headcount
+            .is_some_and(|max| row.headcount > max)
+        {
+            return Some(StopReason::MaxHeadcount);
+        }
+        if let Some(plateau) = &self.cfg.plateau {
+            self.brain_volumes.push_back(row.avg_brain_volume);
+            if self.brain_volumes.len() > plateau.window {
+                self.brain_volumes.pop_front();
+            }
+            if plateau.window > 1
+                && self.brain_volumes.len() == plateau.window
+                && slope(&self.brain_volumes).abs() < plateau.epsilon
+            {
+                return Some(StopReason::Plateau);
+            }
+        }
+        if self
+            .cfg
+            .wall_clock_seconds
+            .is_some_and(|limit| self.started.elapsed().as_secs() >= limit)
+        {
+            return Some(StopReason::WallClock);
+        }
+        if let Some(expression) = &self.expression {
+            if expression.holds(&|name| row.column(name)) {
+                return Some(StopReason::Expression);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slope_of_series() {
+        let cases: [(&[f64], f64); 5] = [
+            (&[], 0.0),
+            (&[3.0], 0.0),
+            (&[1.0, 1.0, 1.0], 0.0),
+            (&[0.0, 2.0, 4.0, 6.0], 2.0),
+            (&[5.0, 4.0, 6.0, 5.0], 0.2),
+        ];
+        for (values, expected) in cases {
+            let values: VecDeque<f64> = values.iter().copied().collect();
+            assert!(
+                (slope(&values) - expected).abs() < 1e-12,
+                "{:?}: {} instead of {}",
+                values,
+                slope(&values),
+                expected
+            );
+        }
+    }
+
+    fn row(year: u32, headcount: u64, avg_brain_volume: f64) -> GlobalStatsRow {
+        GlobalStatsRow {
+            simulation_id: String::new(),
+            year,
+            total_memes_known: 3 * headcount,
+            avg_memes_known: 3.0,
+            avg_trick_efficiency: 0.0,
+            avg_brain_volume,
+            avg_meme_size: 1.0,
+            resources: 1000.0,
+            shock: false,
+            max_meme_depth: 1,
+            avg_meme_depth: 1.0,
+            headcount,
+            group_count: 1,
+        }
+    }
+
+    fn monitor_of(cfg: StopCfg) -> StopMonitor {
+        StopMonitor::new(&cfg).unwrap()
+    }
+
+    #[test]
+    fn stops_below_min_headcount() {
+        let mut monitor = monitor_of(StopCfg {
+            min_headcount: Some(10),
+            ..StopCfg::default()
+        });
+        assert_eq!(monitor.check(&row(0, 10, 1.0)), None);
+        assert_eq!(
+            monitor.check(&row(1, 9, 1.0)),
+            Some(StopReason::MinHeadcount)
+        );
+    }
+
+    #[test]
+    fn stops_above_max_headcount() {
+        let mut monitor = monitor_of(StopCfg {
+            max_headcount: Some(100),
+            ..StopCfg::default()
+        });
+        assert_eq!(monitor.check(&row(0, 100, 1.0)), None);
+        assert_eq!(
+            monitor.check(&row(1, 101, 1.0)),
+            Some(StopReason::MaxHeadcount)
+        );
+    }
+
+    #[test]
+    fn stops_on_a_brain_volume_plateau() {
+        let mut monitor = monitor_of(StopCfg {
+            plateau: Some(PlateauCfg {
+                window: 3,
+                epsilon: 0.1,
+            }),
+            ..StopCfg::default()
+        });
+        // Slopes 1.0 and 0.5 over the last three years, then 0.0
+        let volumes = [1.0, 2.0, 3.0, 3.0, 3.0];
+        let reasons: Vec<_> = volumes
+            .iter()
+            .enumerate()
+            .map(|(year, &volume)| monitor.check(&row(year as u32, 50, volume)))
+            .collect();
+        assert_eq!(reasons, [None, None, None, None, Some(StopReason::Plateau)]);
+    }
+
+    #[test]
+    fn stops_when_the_wall_clock_budget_is_spent() {
+        let mut unlimited = monitor_of(StopCfg {
+            wall_clock_seconds: Some(3600),
+            ..StopCfg::default()
+        });
+        assert_eq!(unlimited.check(&row(0, 50, 1.0)), None);
+        let mut spent = monitor_of(StopCfg {
+            wall_clock_seconds: Some(0),
+            ..StopCfg::default()
+        });
+        assert_eq!(spent.check(&row(0, 50, 1.0)), Some(StopReason::WallClock));
+    }
+
+    #[test]
+    fn stops_when_the_expression_holds() {
+        let mut monitor = monitor_of(StopCfg {
+            expression: Some("headcount > 40 && year >= 10".to_string()),
+            ..StopCfg::default()
+        });
+        assert_eq!(monitor.check(&row(9, 50, 1.0)), None);
+        assert_eq!(monitor.check(&row(10, 40, 1.0)), None);
+        assert_eq!(
+            monitor.check(&row(10, 50, 1.0)),
+            Some(StopReason::Expression)
+        );
+
+        // 0 / 0 is not a reason to stop
+        let mut ratio = monitor_of(StopCfg {
+            expression: Some("total_memes_known / headcount".to_string()),
+            ..StopCfg::default()
+        });
+        assert_eq!(ratio.check(&row(0, 0, 0.0)), None);
+        assert_eq!(ratio.check(&row(1, 2, 1.0)), Some(StopReason::Expression));
+    }
+}
@@ -1,19 +1,42 @@
+use rand::seq::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fmt;
 
 use crate::config::config::*;
 use crate::simulation::memetics::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Alleles {
     pub allele1: f64,
     pub allele2: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sex {
+    Female,
+    Male,
+    /// Agents have no sex unless `MatingCfg::sexes` is enabled.
+    Unspecified,
+}
+
+impl Sex {
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        if rng.gen_bool(0.5) {
+            Sex::Female
+        } else {
+            Sex::Male
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Agent {
     pub id: usize,
     pub age: u8,
+    /// Used for mating and sex-biased migration when sexes are enabled.
+    pub sex: Sex,
     pub hunger_counter: u8,
     pub mc_alleles: Alleles,
     pub le_alleles: Alleles,
@@ -24,10 +47,20 @@ pub struct Agent {
     pub tot_hunting_efficiency: f64,
     pub useless_probability: f64,
     pub trick_efficiency: f64,
+    pub warfare_efficiency: f64,
+    pub resource_cost_modifier: f64,
+    pub mortality_modifier: f64,
+    pub fertility_modifier: f64,
     pub memory_used: f64,
     pub resources: f64,
+    /// Whether the agent could have taken part in this year's hunt but
+    /// skipped it.
+    #[serde(default)]
+    pub shirked: bool,
     pub memes: Vec<Meme>,
     pub meme_id_set: HashSet<usize>,
+    /// Ids of both parents; `None` for founders.
+    pub parents: Option<(usize, usize)>,
     pub config: AgentCfg,
 }
 
@@ -47,6 +80,7 @@ impl Agent {
         Self {
             id: new_id(rng),
             age: 0,
+            sex: Sex::Unspecified,
             hunger_counter: 0,
             mc_alleles: Alleles {
                 allele1: 0.0,
@@ -65,11 +99,17 @@ impl Agent {
             hunting_efficiency: 10.0,
             tot_hunting_efficiency: 10.0,
             trick_efficiency: 0.0,
+            warfare_efficiency: 0.0,
+            resource_cost_modifier: 0.0,
+            mortality_modifier: 0.0,
+            fertility_modifier: 0.0,
             useless_probability: 0.0,
             memory_used: 0.0,
             resources: 10.0,
+            shirked: false,
             memes: Vec::new(),
             meme_id_set: HashSet::new(),
+            parents: None,
             config: cfg,
         }
     }
@@ -88,6 +128,7 @@ impl Agent {
         Self {
             id: new_id(rng),
             age: 0,
+            sex: Sex::Unspecified,
             hunger_counter: 0,
             mc_alleles,
             le_alleles,
@@ -97,11 +138,17 @@ impl Agent {
             tot_hunting_efficiency: 10.0,
             hunting_efficiency: 10.0,
             trick_efficiency: 0.0,
+            warfare_efficiency: 0.0,
+            resource_cost_modifier: 0.0,
+            mortality_modifier: 0.0,
+            fertility_modifier: 0.0,
             useless_probability: 0.0,
             memory_used: 0.0,
             resources: 0.0,
+            shirked: false,
             memes: Vec::new(),
             meme_id_set: HashSet::new(),
+            parents: None,
             config: cfg,
         }
     }
@@ -110,8 +157,188 @@ impl Agent {
         self.config.base_brain_volume + self.config.mem_cost * self.mc_alleles.phenotype()
     }
 
-    pub fn to_string(&self) -> String {
-        format!(
+    pub fn is_parent_of(&self, other: &Agent) -> bool {
+        other
+            .parents
+            .is_some_and(|(a, b)| a == self.id || b == self.id)
+    }
+
+    /// Parents, children and siblings (including half-siblings).
+    pub fn is_kin(&self, other: &Agent) -> bool {
+        if self.is_parent_of(other) || other.is_parent_of(self) {
+            return true;
+        }
+        match (self.parents, other.parents) {
+            (Some((a1, b1)), Some((a2, b2))) => a1 == a2 || a1 == b2 || b1 == a2 || b1 == b2,
+            _ => false,
+        }
+    }
+
+    /// Coefficient of relatedness: 0.5 for parents, children and full
+    /// siblings, 0.25 for half-siblings, 0 otherwise.
+    pub fn relatedness(&self, other: &Agent) -> f64 {
+        if self.is_parent_of(other) || other.is_parent_of(self) {
+            return 0.5;
+        }
+        match (self.parents, other.parents) {
+            (Some((a1, b1)), Some((a2, b2))) => {
+                let shared = [a1, b1].iter().filter(|&&p| p == a2 || p == b2).count();
+                0.25 * shared as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Adds `effect` to the trait targeted by a meme; a negative effect
+    /// undoes a forgotten meme.
+    pub fn apply_effect(&mut self, target: EffectTarget, effect: f64) {
+        match target {
+            EffectTarget::Hunting => {
+                self.tot_hunting_efficiency += effect;
+            }
+            EffectTarget::Learning => {
+                self.tot_learning_efficiency += effect;
+            }
+            EffectTarget::Teaching => {
+                self.tot_teaching_efficiency += effect;
+            }
+            EffectTarget::Trick => {
+                self.trick_efficiency += effect;
+            }
+            EffectTarget::Useless => {
+                self.useless_probability += effect;
+                if self.useless_probability < 0.0 {
+                    self.useless_probability = 0.0;
+                }
+            }
+            EffectTarget::Warfare => {
+                self.warfare_efficiency += effect;
+            }
+            EffectTarget::ResourceCost => {
+                self.resource_cost_modifier += effect;
+            }
+            EffectTarget::Mortality => {
+                self.mortality_modifier += effect;
+            }
+            EffectTarget::Fertility => {
+                self.fertility_modifier += effect;
+            }
+            EffectTarget::None => {}
+        }
+    }
+
+    /// Removes the meme at `idx` together with its effect.
+    fn remove_meme(&mut self, idx: usize) -> Meme {
+        let meme = self.memes.remove(idx);
+        self.apply_effect(meme.target, -meme.increment());
+        self.memory_used -= meme.size;
+        self.meme_id_set.remove(&meme.id);
+        meme
+    }
+
+    /// Forgets the meme at `idx` and every meme improving it, directly or
+    /// not, since improvements only add to the meme they improve. Returns
+    /// the forgotten memes, the one at `idx` first. Improvements are always
+    /// learned after their prerequisite, so no meme before `idx` is removed.
+    pub fn forget(&mut self, idx: usize) -> Vec<Meme> {
+        let mut forgotten = vec![self.remove_meme(idx)];
+        let mut next = 0;
+        while next < forgotten.len() {
+            let id = forgotten[next].id;
+            while let Some(dependant) = self.memes.iter().position(|m| m.prerequisite == Some(id)) {
+                forgotten.push(self.remove_meme(dependant));
+            }
+            next += 1;
+        }
+        forgotten
+    }
+
+    /// The known meme with id `id` and the chain of memes it improves.
+    fn prerequisite_chain(&self, id: Option<usize>) -> Vec<&Meme> {
+        let mut chain = Vec::new();
+        let mut next = id;
+        while let Some(meme) = next.and_then(|id| self.memes.iter().find(|m| m.id == id)) {
+            chain.push(meme);
+            next = meme.prerequisite;
+        }
+        chain
+    }
+
+    /// Forgets memes chosen by `policy` until `m` fits into memory and
+    /// returns them. Nothing is forgotten when `m` could not be learned anyway,
+    /// e.g. because it does not fit next to its prerequisites.
+    pub fn make_room<R: Rng + ?Sized>(
+        &mut self,
+        m: &Meme,
+        policy: MemoryPolicy,
+        rng: &mut R,
+    ) -> Vec<Meme> {
+        let capacity = self.mc_alleles.phenotype();
+        // Forgetting any meme of the chain would forget the prerequisite
+        let chain: HashSet<usize> = self
+            .prerequisite_chain(m.prerequisite)
+            .iter()
+            .map(|known| known.id)
+            .collect();
+        let kept: f64 = self
+            .memes
+            .iter()
+            .filter(|known| chain.contains(&known.id))
+            .map(|known| known.size)
+            .sum();
+        let mut forgotten = Vec::new();
+        if policy == MemoryPolicy::Reject
+            || capacity - self.memory_used >= m.size
+            || capacity - kept < m.size
+            || m.prerequisite
+                .is_some_and(|p| !self.meme_id_set.contains(&p))
+        {
+            return forgotten;
+        }
+        while capacity - self.memory_used < m.size {
+            // the prerequisites of the new meme have to stay
+            let candidates: Vec<usize> = (0..self.memes.len())
+                .filter(|&i| !chain.contains(&self.memes[i].id))
+                .collect();
+            let idx = match policy {
+                MemoryPolicy::ForgetLeastEffective => candidates
+                    .iter()
+                    .copied()
+                    .min_by(|&a, &b| self.memes[a].effect.total_cmp(&self.memes[b].effect)),
+                // memes are stored in the order they were learned
+                MemoryPolicy::ForgetOldest => candidates.first().copied(),
+                MemoryPolicy::ForgetRandom => candidates.choose(rng).copied(),
+                MemoryPolicy::Reject => None,
+            };
+            match idx {
+                Some(idx) => forgotten.extend(self.forget(idx)),
+                None => break,
+            }
+        }
+        forgotten
+    }
+
+    pub fn try_learning(&mut self, m: Meme) -> bool {
+        if let Some(prerequisite) = m.prerequisite {
+            if !self.meme_id_set.contains(&prerequisite) {
+                return false;
+            }
+        }
+        if self.mc_alleles.phenotype() - self.memory_used < m.size {
+            return false;
+        }
+        self.memory_used += m.size;
+        self.memes.push(m);
+        self.meme_id_set.insert(m.id);
+        self.apply_effect(m.target, m.increment());
+        true
+    }
+}
+
+impl fmt::Display for Agent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "id: {}, age: {}, h: {}, mc: {}, le: {}, te: {}, he: {}, tre: {}, up: {}, bv: {}, res: {}",
             self.id,
             self.age,
@@ -126,31 +353,141 @@ impl Agent {
             self.resources
         )
     }
+}
 
-    pub fn try_learning(&mut self, m: Meme) -> bool {
-        if self.mc_alleles.phenotype() - self.memory_used < m.size {
-            return false;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn meme(id: usize, size: f64, effect: f64, prerequisite: Option<usize>) -> Meme {
+        Meme {
+            id,
+            size,
+            kind: 0,
+            target: EffectTarget::Hunting,
+            effect,
+            base_effect: 0.0,
+            complexity: 0.0,
+            prerequisite,
+            depth: prerequisite.map_or(0, |_| 1),
         }
-        self.memory_used += m.size;
-        self.memes.push(m);
-        self.meme_id_set.insert(m.id);
-        match m.kind {
-            MemeType::Hunting => {
-                self.tot_hunting_efficiency += m.effect;
-            }
-            MemeType::Learning => {
-                self.tot_learning_efficiency += m.effect;
-            }
-            MemeType::Teaching => {
-                self.tot_teaching_efficiency += m.effect;
-            }
-            MemeType::Trick => {
-                self.trick_efficiency += m.effect;
-            }
-            MemeType::Useless => {
-                self.useless_probability += m.effect;
-            }
+    }
+
+    /// Agent with `capacity` units of memory who learned `memes` in order.
+    fn agent_knowing(capacity: f64, memes: &[Meme]) -> Agent {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        let mut agent = Agent::default(&mut rng, AgentCfg::default());
+        agent.mc_alleles = Alleles {
+            allele1: capacity,
+            allele2: capacity,
+        };
+        for &m in memes {
+            assert!(agent.try_learning(m));
         }
-        true
+        agent
+    }
+
+    fn ids(memes: &[Meme]) -> Vec<usize> {
+        memes.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn nothing_is_forgotten_for_a_meme_that_cannot_fit_next_to_its_prerequisite() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(7);
+        let mut agent = agent_knowing(3.0, &[meme(1, 2.0, 1.0, None), meme(2, 1.0, 1.0, None)]);
+        let improvement = meme(3, 2.0, 2.0, Some(1));
+        let forgotten = agent.make_room(&improvement, MemoryPolicy::ForgetOldest, &mut rng);
+        assert!(forgotten.is_empty());
+        assert_eq!(ids(&agent.memes), [1, 2]);
+        assert!(!agent.try_learning(improvement));
+
+        // A smaller improvement fits once the other meme is forgotten
+        let improvement = meme(3, 1.0, 2.0, Some(1));
+        let forgotten = agent.make_room(&improvement, MemoryPolicy::ForgetOldest, &mut rng);
+        assert_eq!(ids(&forgotten), [2]);
+        assert!(agent.try_learning(improvement));
+        assert_eq!(ids(&agent.memes), [1, 3]);
+    }
+
+    #[test]
+    fn forgetting_a_prerequisite_forgets_its_improvements() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(7);
+        let double = Range { min: 2.0, max: 2.0 };
+        let base = meme(1, 1.0, 3.0, None);
+        let improved = base.improved(double, &mut rng);
+        let twice_improved = improved.improved(double, &mut rng);
+        let other = meme(2, 1.0, 1.0, None);
+        let mut agent = agent_knowing(5.0, &[base, other, improved, twice_improved]);
+        let hunting = agent.tot_hunting_efficiency;
+        // Each improvement replaces the effect of the meme it improves
+        let initial = hunting - 12.0 - 1.0;
+        assert!((agent.memory_used - 4.0).abs() < 1e-12);
+
+        let forgotten = agent.forget(0);
+        assert_eq!(ids(&forgotten), [1, improved.id, twice_improved.id]);
+        assert_eq!(ids(&agent.memes), [2]);
+        assert!((agent.tot_hunting_efficiency - (initial + 1.0)).abs() < 1e-12);
+        assert!((agent.memory_used - 1.0).abs() < 1e-12);
+        assert!(!agent.meme_id_set.contains(&improved.id));
+
+        // Forgetting an improvement keeps what it improves
+        let mut agent = agent_knowing(5.0, &[base, improved, twice_improved]);
+        assert_eq!(ids(&agent.forget(1)), [improved.id, twice_improved.id]);
+        assert_eq!(ids(&agent.memes), [1]);
+        assert!((agent.tot_hunting_efficiency - (initial + 3.0)).abs() < 1e-12);
+
+        // Making room keeps the whole chain below the new improvement
+        let mut agent = agent_knowing(3.0, &[base, improved, other]);
+        let forgotten = agent.make_room(&twice_improved, MemoryPolicy::ForgetOldest, &mut rng);
+        assert_eq!(ids(&forgotten), [2]);
+        assert!(agent.try_learning(twice_improved));
+        assert_eq!(agent.memes.iter().map(|m| m.depth).max(), Some(2));
+    }
+
+    #[test]
+    fn memory_policies_choose_what_is_forgotten() {
+        let known = [
+            meme(1, 1.0, 3.0, None),
+            meme(2, 1.0, 1.0, None),
+            meme(3, 1.0, 2.0, None),
+        ];
+        let new = meme(4, 2.0, 1.0, None);
+        let cases = [
+            (MemoryPolicy::Reject, vec![]),
+            (MemoryPolicy::ForgetOldest, vec![1, 2]),
+            (MemoryPolicy::ForgetLeastEffective, vec![2, 3]),
+        ];
+        for (policy, expected) in cases {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(7);
+            let mut agent = agent_knowing(3.0, &known);
+            let forgotten = agent.make_room(&new, policy, &mut rng);
+            assert_eq!(ids(&forgotten), expected, "{:?}", policy);
+            assert_eq!(
+                agent.try_learning(new),
+                !expected.is_empty(),
+                "{:?}",
+                policy
+            );
+        }
+
+        // Random forgetting is reproducible with the same seed
+        let forget_randomly = |seed| {
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+            let mut agent = agent_knowing(3.0, &known);
+            ids(&agent.make_room(&new, MemoryPolicy::ForgetRandom, &mut rng))
+        };
+        assert_eq!(forget_randomly(11).len(), 2);
+        assert_eq!(forget_randomly(11), forget_randomly(11));
+
+        // A meme larger than the whole memory costs nothing
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(7);
+        let mut agent = agent_knowing(3.0, &known);
+        let huge = meme(5, 4.0, 1.0, None);
+        assert!(agent
+            .make_room(&huge, MemoryPolicy::ForgetOldest, &mut rng)
+            .is_empty());
+        assert_eq!(agent.memes.len(), 3);
     }
 }
@@ -0,0 +1,161 @@
+use crate::config::config::*;
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use std::f64::consts::PI;
+
+/// Resources realized in a single year.
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentState {
+    pub resources: f64,
+    pub shock: bool,
+}
+
+/// Produces the total resources of every year from the configured schedule,
+/// cycle, noise and shocks.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    pub base_resources: f64,
+    pub config: EnvironmentCfg,
+    noise_distribution: Option<Normal<f64>>,
+    noise: f64,
+    shock_years_left: usize,
+}
+
+impl Environment {
+    /// Panics on a negative or NaN `noise.sigma`, which
+    /// `SimConfig::validate` rejects.
+    pub fn new(base_resources: f64, cfg: EnvironmentCfg) -> Self {
+        let noise_distribution = cfg
+            .noise
+            .map(|noise| Normal::new(0.0, noise.sigma).expect("Invalid normal parameters"));
+        Self {
+            base_resources,
+            config: cfg,
+            noise_distribution,
+            noise: 0.0,
+            shock_years_left: 0,
+        }
+    }
+
+    fn scheduled(&self, year: usize) -> f64 {
+        self.config
+            .schedule
+            .iter()
+            .filter(|point| point.from_year <= year)
+            .max_by_key(|point| point.from_year)
+            .map_or(self.base_resources, |point| point.resources)
+    }
+
+    /// Advances the stochastic components by one year and returns the
+    /// resources available in `year`.
+    pub fn advance<R: Rng + ?Sized>(&mut self, year: usize, rng: &mut R) -> EnvironmentState {
+        let mut resources = self.scheduled(year);
+
+        if let Some(cycle) = self.config.cycle {
+            if cycle.period > 0.0 {
+                let angle = 2.0 * PI * year as f64 / cycle.period + cycle.phase;
+                resources *= 1.0 + cycle.amplitude * angle.sin();
+            }
+        }
+
+        if let (Some(noise), Some(normal)) = (self.config.noise, self.noise_distribution) {
+            self.noise = noise.phi * self.noise + normal.sample(rng);
+            resources *= 1.0 + self.noise;
+        }
+
+        let mut shock = false;
+        if let Some(shocks) = self.config.shocks {
+            if self.shock_years_left == 0 && rng.gen::<f64>() < shocks.probability {
+                self.shock_years_left = shocks.duration.max(1);
+            }
+            if self.shock_years_left > 0 {
+                self.shock_years_left -= 1;
+                resources *= 1.0 - shocks.severity.clamp(0.0, 1.0);
+                shock = true;
+            }
+        }
+
+        EnvironmentState {
+            resources: resources.max(0.0),
+            shock,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_xoshiro::rand_core::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    fn series(cfg: &EnvironmentCfg, seed: u64, years: usize) -> Vec<EnvironmentState> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let mut environment = Environment::new(100.0, cfg.clone());
+        (0..years)
+            .map(|year| environment.advance(year, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn schedule_and_cycle_are_deterministic() {
+        let cfg = EnvironmentCfg {
+            schedule: vec![SchedulePoint {
+                from_year: 4,
+                resources: 200.0,
+            }],
+            cycle: Some(CycleCfg {
+                amplitude: 0.5,
+                period: 4.0,
+                phase: 0.0,
+            }),
+            ..EnvironmentCfg::default()
+        };
+        let resources: Vec<f64> = series(&cfg, 1, 8).iter().map(|s| s.resources).collect();
+        let expected = [100.0, 150.0, 100.0, 50.0, 200.0, 300.0, 200.0, 100.0];
+        for (year, (actual, expected)) in resources.iter().zip(expected).enumerate() {
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "year {}: {}",
+                year,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn noise_and_shocks_follow_the_seed() {
+        let cfg = EnvironmentCfg {
+            noise: Some(NoiseCfg {
+                phi: 0.8,
+                sigma: 0.1,
+            }),
+            shocks: Some(ShockCfg {
+                probability: 0.2,
+                severity: 0.5,
+                duration: 3,
+            }),
+            ..EnvironmentCfg::default()
+        };
+        let resources = |states: &[EnvironmentState]| -> Vec<f64> {
+            states.iter().map(|s| s.resources).collect()
+        };
+        let states = series(&cfg, 8, 300);
+        assert_eq!(resources(&states), resources(&series(&cfg, 8, 300)));
+        assert_ne!(resources(&states), resources(&series(&cfg, 9, 300)));
+        assert!(states.iter().all(|s| s.resources >= 0.0));
+
+        // A shock lasts `duration` years and another one may follow right
+        // away, so that runs of shock years come in multiples of 3
+        let shocks: Vec<bool> = states.iter().map(|s| s.shock).collect();
+        let runs: Vec<usize> = shocks
+            .split(|&shock| !shock)
+            .map(|run| run.len())
+            .filter(|&len| len > 0)
+            .collect();
+        assert!(runs.len() > 5);
+        let (last, complete) = runs.split_last().unwrap();
+        assert!(complete.iter().all(|len| len % 3 == 0), "{:?}", runs);
+        assert!(*last % 3 == 0 || shocks[299]);
+    }
+}
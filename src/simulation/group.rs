@@ -3,11 +3,14 @@ use crate::simulation::agent::*;
 use crate::config::config::*;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Group {
     pub id: usize,
     pub members: Vec<Agent>,
+    /// Patch of the landscape the group lives on, if the landscape is enabled.
+    pub patch: Option<usize>,
     pub config: GroupCfg,
 }
 
@@ -29,19 +32,17 @@ impl Group {
         }
         Self {
             id: new_id(rng),
-            members: members,
+            members,
+            patch: None,
             config: group_cfg,
         }
     }
 
-    pub fn splinter<R: Rng + ?Sized>(
-        members: &Vec<Agent>,
-        group_cfg: GroupCfg,
-        rng: &mut R,
-    ) -> Self {
+    pub fn splinter<R: Rng + ?Sized>(members: &[Agent], group_cfg: GroupCfg, rng: &mut R) -> Self {
         Self {
             id: new_id(rng),
-            members: members.clone(),
+            members: members.to_vec(),
+            patch: None,
             config: group_cfg,
         }
     }
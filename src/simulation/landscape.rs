@@ -0,0 +1,72 @@
+use crate::config::config::*;
+
+use rand::Rng;
+
+/// A rectangular grid of patches. Patches are indexed row by row and each of
+/// them produces a fixed fraction of the yearly resources.
+#[derive(Debug, Clone)]
+pub struct Landscape {
+    pub width: usize,
+    pub height: usize,
+    /// Fraction of the yearly resources produced by each patch; sums to 1.
+    pub richness: Vec<f64>,
+    pub config: LandscapeCfg,
+}
+
+impl Landscape {
+    pub fn new<R: Rng + ?Sized>(cfg: LandscapeCfg, rng: &mut R) -> Self {
+        let width = cfg.width.max(1);
+        let height = cfg.height.max(1);
+        let variation = cfg.richness_variation.clamp(0.0, 1.0);
+        let mut richness: Vec<f64> = (0..width * height)
+            .map(|_| 1.0 + variation * rng.gen_range(-1.0..=1.0))
+            .collect();
+        let total: f64 = richness.iter().sum();
+        if total > f64::EPSILON {
+            richness.iter_mut().for_each(|r| *r /= total);
+        } else {
+            let n = richness.len() as f64;
+            richness.iter_mut().for_each(|r| *r = 1.0 / n);
+        }
+        Self {
+            width,
+            height,
+            richness,
+            config: cfg,
+        }
+    }
+
+    pub fn patch_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn coords(&self, patch: usize) -> (usize, usize) {
+        (patch % self.width, patch / self.width)
+    }
+
+    /// Chebyshev distance, so that all eight surrounding patches are adjacent.
+    pub fn distance(&self, a: usize, b: usize) -> usize {
+        let (ax, ay) = self.coords(a);
+        let (bx, by) = self.coords(b);
+        ax.abs_diff(bx).max(ay.abs_diff(by))
+    }
+
+    pub fn centre(&self) -> usize {
+        (self.height / 2) * self.width + self.width / 2
+    }
+
+    pub fn random_patch<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        rng.gen_range(0..self.patch_count())
+    }
+
+    /// A random patch adjacent to `patch`, or `patch` itself on a 1x1 grid.
+    pub fn random_neighbour<R: Rng + ?Sized>(&self, patch: usize, rng: &mut R) -> usize {
+        let neighbours: Vec<usize> = (0..self.patch_count())
+            .filter(|&p| self.distance(patch, p) == 1)
+            .collect();
+        if neighbours.is_empty() {
+            return patch;
+        }
+        neighbours[rng.gen_range(0..neighbours.len())]
+    }
+}
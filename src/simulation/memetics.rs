@@ -1,79 +1,91 @@
+use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 
 use crate::config::config::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Meme {
     pub id: usize,
     pub size: f64,
-    pub kind: MemeType,
+    /// Index of the meme kind in `SimConfig::meme_kinds`.
+    pub kind: usize,
+    pub target: EffectTarget,
     pub effect: f64,
+    /// Effect of the improved meme when this one was created, so that only
+    /// the difference is added to an agent who knows both.
+    #[serde(default)]
+    pub base_effect: f64,
+    /// How hard the meme is to learn, independently of its size.
+    pub complexity: f64,
+    /// Id of the meme this one improves; it has to be known to learn this one.
+    pub prerequisite: Option<usize>,
+    /// Length of the chain of improvements leading to this meme.
+    pub depth: u32,
 }
 
+/// Lower bound of the size of mutated memes, so that no meme is free to hold.
+const MIN_MEME_SIZE: f64 = 0.01;
+
 fn new_id<R: Rng + ?Sized>(rng: &mut R) -> usize {
     rng.gen::<usize>()
 }
 
 impl Meme {
-    /// Creates a new `Meme` with random type, effect, and size.
+    /// What knowing this meme adds to its target, on top of the memes it
+    /// improves.
+    pub fn increment(&self) -> f64 {
+        self.effect - self.base_effect
+    }
+
+    /// Creates a new `Meme` of a random configured kind, with effect and size
+    /// drawn as described by the matching `meme_config` entry.
     ///
-    /// - `id`: Unique ID for the meme.
-    /// - `min_size`, `max_size`: Range for meme size; actual size is
-    ///   sampled from a normal distribution centered at the midpoint and
-    ///   clamped to [min_size, max_size].
-    /// - `rng`: A mutable reference to any RNG implementing `rand::Rng`.
-    pub fn new_random<R: Rng + ?Sized>(rng: &mut R) -> Meme {
-        // TODO: pack all arbitrary values into a configuration struct
-        // 1. Pick a random MemeType
-        let kind_index = rng.gen_range(0..=4);
-        let kind = match kind_index {
-            0 => MemeType::Hunting,
-            1 => MemeType::Learning,
-            2 => MemeType::Teaching,
-            3 => MemeType::Trick,
-            _ => MemeType::Useless,
-        };
-        let min_size: f64 = 0.3;
-        let max_size: f64 = 0.8;
-
-        // 2. Assign an effect range per MemeType (example ranges)
-        let (effect_min, effect_max) = match kind {
-            MemeType::Hunting => (1.3, 6.2),
-            MemeType::Learning => (0.3, 0.8),
-            MemeType::Teaching => (0.09, 0.6),
-            MemeType::Trick => (0.9, 2.8),
-            MemeType::Useless => (0.1, 0.4),
-        };
-        // Generate effect within that range
-        let effect = rng.gen_range(effect_min..effect_max);
-
-        // 3. Sample size from a normal distribution clamped to [min_size, max_size]
-        //    We'll center the normal at the midpoint and derive std as a fraction
-        //    of the total range. Adjust these details as you prefer.
-        let mean = (min_size + max_size) / 2.0;
-        let std = (max_size - min_size) / 6.0; // e.g., ~99.7% values within [min_size, max_size]
-        let normal_dist = Normal::new(mean, std).expect("Invalid normal distribution parameters.");
-        let mut size = normal_dist.sample(rng);
-        size = size.clamp(min_size, max_size);
+    /// Returns `None` if no memes are configured or the entry refers to an
+    /// unknown kind.
+    pub fn new_random<R: Rng + ?Sized>(cfg: &SimConfig, rng: &mut R) -> Option<Meme> {
+        let meme_cfg = cfg.meme_config.choose(rng)?;
+        Meme::from_config(cfg, meme_cfg, rng)
+    }
 
-        Meme {
-            id: new_id(rng),
-            size: size,
-            kind: kind,
-            effect: effect,
+    /// Creates a new `Meme` as described by one of the `meme_config` entries.
+    pub fn from_config<R: Rng + ?Sized>(
+        cfg: &SimConfig,
+        meme_cfg: &MemeConfig,
+        rng: &mut R,
+    ) -> Option<Meme> {
+        let kind = cfg.meme_kind_index(&meme_cfg.meme_kind)?;
+        let mut meme = Meme::new_typed(
+            kind,
+            cfg.meme_kinds[kind].target,
+            meme_cfg.size,
+            meme_cfg.effect,
+            meme_cfg.size_effect_corr,
+            rng,
+        );
+        if meme_cfg.complexity.max > meme_cfg.complexity.min {
+            meme.complexity = rng.gen_range(meme_cfg.complexity.min..meme_cfg.complexity.max);
+        } else {
+            meme.complexity = meme_cfg.complexity.min;
         }
+        Some(meme)
     }
 
+    /// Creates a meme of kind `kind` (an index into `meme_kinds`) with size
+    /// and effect drawn from correlated normal distributions clamped to the
+    /// given ranges.
     pub fn new_typed<R: Rng + ?Sized>(
-        kind: MemeType,
-        min_size: f64,
-        max_size: f64,
-        min_effect: f64,
-        max_effect: f64,
+        kind: usize,
+        target: EffectTarget,
+        size: Range,
+        effect: Range,
         correlation: f64,
         rng: &mut R,
     ) -> Meme {
+        let (min_size, max_size) = (size.min, size.max);
+        let (min_effect, max_effect) = (effect.min, effect.max);
+
         // 1. We'll treat size and effect as correlated normal variables.
         //    First, define each variable's mean and std dev:
         let mean_size = (min_size + max_size) / 2.0;
@@ -107,7 +119,48 @@ impl Meme {
             id: new_id(rng),
             size: raw_size,
             kind,
+            target,
             effect: raw_effect,
+            base_effect: 0.0,
+            complexity: 0.0,
+            prerequisite: None,
+            depth: 0,
+        }
+    }
+
+    /// Creates a cultural mutant of this meme: a new meme of the same kind
+    /// with normal noise added to its effect and size. Panics on a negative
+    /// or NaN standard deviation, which `SimConfig::validate` rejects.
+    pub fn mutated<R: Rng + ?Sized>(&self, effect_std: f64, size_std: f64, rng: &mut R) -> Meme {
+        let effect_noise = Normal::new(0.0, effect_std).expect("Invalid normal parameters");
+        let size_noise = Normal::new(0.0, size_std).expect("Invalid normal parameters");
+        Meme {
+            id: new_id(rng),
+            size: (self.size + size_noise.sample(rng)).max(MIN_MEME_SIZE),
+            effect: (self.effect + effect_noise.sample(rng)).max(0.0),
+            ..*self
+        }
+    }
+
+    /// Creates an improvement of this meme: a meme of the same kind and size
+    /// with the effect multiplied by a factor drawn from `factor`, which can
+    /// only be learned by those who know this meme.
+    pub fn improved<R: Rng + ?Sized>(&self, factor: Range, rng: &mut R) -> Meme {
+        let factor = if factor.max > factor.min {
+            rng.gen_range(factor.min..factor.max)
+        } else {
+            factor.min
+        };
+        Meme {
+            id: new_id(rng),
+            size: self.size,
+            kind: self.kind,
+            target: self.target,
+            effect: self.effect * factor,
+            base_effect: self.effect,
+            complexity: self.complexity,
+            prerequisite: Some(self.id),
+            depth: self.depth + 1,
         }
     }
 }
@@ -1,3 +1,5 @@
 pub mod agent;
+pub mod environment;
 pub mod group;
+pub mod landscape;
 pub mod memetics;
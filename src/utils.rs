@@ -3,3 +3,81 @@ use uuid::Uuid;
 pub fn generate_uuid() -> String {
     Uuid::new_v4().to_string()
 }
+
+/// Crate version and the git commit it was built from.
+pub fn code_version() -> String {
+    format!(
+        "{}+{}",
+        env!("CARGO_PKG_VERSION"),
+        env!("TRIBESIM_GIT_HASH")
+    )
+}
+
+/// Name of the machine the simulation runs on.
+pub fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Borrows two distinct elements of a slice mutably at the same time.
+pub fn get_two_mut<T>(slice: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
+    assert!(i != j, "Indices must differ");
+    if i < j {
+        let (left, right) = slice.split_at_mut(j);
+        (&mut left[i], &mut right[0])
+    } else {
+        let (left, right) = slice.split_at_mut(i);
+        (&mut right[0], &mut left[j])
+    }
+}
+
+/// Gini coefficient of a sample of non-negative values.
+///
+/// The slice is sorted in place. Empty samples and samples summing to zero
+/// are treated as perfectly equal.
+pub fn gini_coefficient(values: &mut [f64]) -> f64 {
+    let n = values.len() as f64;
+    let total: f64 = values.iter().sum();
+    if values.is_empty() || total <= f64::EPSILON {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let weighted: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as f64 + 1.0) * v)
+        .sum();
+    2.0 * weighted / (n * total) - (n + 1.0) / n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gini_of_samples() {
+        let cases: [(&[f64], f64); 7] = [
+            (&[], 0.0),
+            (&[0.0, 0.0], 0.0),
+            (&[7.0], 0.0),
+            (&[5.0, 5.0, 5.0], 0.0),
+            (&[1.0, 2.0, 3.0, 4.0], 0.25),
+            (&[4.0, 1.0, 3.0, 2.0], 0.25),
+            (&[0.0, 0.0, 0.0, 4.0], 0.75),
+        ];
+        for (values, expected) in cases {
+            let gini = gini_coefficient(&mut values.to_vec());
+            assert!(
+                (gini - expected).abs() < 1e-12,
+                "{:?}: {} instead of {}",
+                values,
+                gini,
+                expected
+            );
+        }
+    }
+}